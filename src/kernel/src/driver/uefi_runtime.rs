@@ -229,6 +229,11 @@ pub struct Time {
     pub pad2: u8,
 }
 
+impl Time {
+    /// The time zone is unknown, the time is then assumed to be UTC
+    pub const UNSPECIFIED_TIMEZONE: i16 = 0x07FF;
+}
+
 bitflags! {
     /// A bitmask containing daylight savings time information.
     #[repr(transparent)]
@@ -337,8 +342,8 @@ impl UefiRuntime {
                 mapper.map_to_auto(page, ufu_stuff.phys_start.into(), page_count, ufu_stuff.att.into());
             });
             ufu_stuff.virt_start = page.start_address();
-            if ufu_stuff.phys_start < runtime_table_raw
-                && (ufu_stuff.phys_start + ufu_stuff.phys_start.as_u64() * PAGE_SIZE - 1) > runtime_table_raw
+            if ufu_stuff.phys_start <= runtime_table_raw
+                && (ufu_stuff.phys_start + ufu_stuff.page_count * PAGE_SIZE - 1) >= runtime_table_raw
             {
                 runtime_table = page.start_address() + (runtime_table_raw.as_u64() - ufu_stuff.phys_start.as_u64());
             }
@@ -378,14 +383,14 @@ impl UefiRuntime {
         Self { table: runtime_table }
     }
 
-    pub fn get_time(&self) -> Time {
+    pub fn get_time(&self) -> Result<Time, EfiStatus> {
         let mut time = MaybeUninit::<Time>::uninit();
         let status =
             unsafe { ((*self.table.runtime_services).get_time)(time.as_mut_ptr().cast(), core::ptr::null_mut()) };
         if status != EfiStatus::SUCCESS {
-            panic!("Failed to set virtual address map for uefi {status:?}");
+            return Err(status);
         }
-        unsafe { time.assume_init() }
+        Ok(unsafe { time.assume_init() })
     }
 
     pub fn reset(&self, rt: ResetType, status: EfiStatus) -> ! {
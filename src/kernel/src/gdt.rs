@@ -1,7 +1,7 @@
 use alloc::boxed::Box;
 use kernel_proc::{def_local, local_builder};
 use pager::PrivilegeLevel;
-use pager::gdt::{DOUBLE_FAULT_IST_INDEX, Descriptor, GENERAL_STACK_INDEX, Gdt, TaskStateSegment};
+use pager::gdt::{DOUBLE_FAULT_IST_INDEX, Descriptor, Gdt, TaskStateSegment};
 use pager::registers::{CS, SS, load_tss};
 
 use crate::initialization_context::{InitializationContext, Stage3};
@@ -23,8 +23,6 @@ pub fn init_gdt(ctx: &mut InitializationContext<Stage3>) {
         let double_fault = ctx
             .stack_allocator(|mut s| s.alloc_stack_kernel())
             .expect("Failed to allocate stack for the double fault handler");
-        let general_stack =
-            ctx.stack_allocator(|mut s| s.alloc_stack_kernel()).expect("Failed to allocate general interrupt stack");
         let rsp0_stack = ctx
             .stack_allocator(|mut s| s.alloc_stack_kernel())
             .expect("Failed to allocate stack for rsp0 privilage change in TSS");
@@ -32,7 +30,6 @@ pub fn init_gdt(ctx: &mut InitializationContext<Stage3>) {
         log!(Debug, "Initializing gdt for core: {id}");
         let tss = Box::leak(TaskStateSegment::new().into());
         tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = double_fault.top();
-        tss.interrupt_stack_table[GENERAL_STACK_INDEX as usize] = general_stack.top();
         tss.privilege_stack_table[0] = rsp0_stack.top();
         let gdt = Box::leak(Gdt::new().into());
         let kernel_code_selector = gdt.add_entry(Descriptor::code_segment(), PrivilegeLevel::Ring0);
@@ -23,11 +23,13 @@ use crate::smp::CoreId;
 use crate::smp::CpuLocalBuilder;
 use crate::smp::cpu_local_avaiable;
 use crate::syscall::IS_IN_SYSCALL;
+use crate::userland::fault::UserFault;
 use crate::userland::pipeline;
 use crate::userland::pipeline::CommonRequestContext;
 use crate::userland::pipeline::CommonRequestStackFrame;
 use crate::userland::pipeline::RequestReferer;
 use crate::userland::pipeline::dispatch::DispatchAction;
+use crate::userland::user_memory;
 use alloc::boxed::Box;
 use alloc::collections::vec_deque::VecDeque;
 use alloc::sync::Arc;
@@ -44,7 +46,6 @@ use kernel_proc::{fill_idt, generate_interrupt_handlers};
 use pager::PrivilegeLevel;
 use pager::address::VirtAddr;
 use pager::gdt::DOUBLE_FAULT_IST_INDEX;
-use pager::gdt::GENERAL_STACK_INDEX;
 use pager::registers::Cr2;
 use pager::registers::GsBase;
 use pager::registers::RFlags;
@@ -111,10 +112,12 @@ fn disable_pic(ctx: &mut InitializationContext<Stage3>) {
 fn create_idt() -> &'static Idt {
     let idt = Box::leak(Idt::new().into());
 
+    // The faults don't switch to an interrupt stack, a user fault runs the pipeline which can fault
+    // again (e.g. a recovered user copy), and the same interrupt stack would be reset under it
     unsafe {
-        idt.general_protection.set_handler_fn(general_protection_fault_handler).set_stack_index(GENERAL_STACK_INDEX);
-        idt.page_fault.set_handler_fn(page_fault_handler).set_stack_index(GENERAL_STACK_INDEX);
-        //idt.invalid_opcode.set_handler_fn(invalid_opcode).set_stack_index(GENERAL_STACK_INDEX);
+        idt.general_protection.set_handler_addr(VirtAddr::new(general_protection_fault_handler as *const () as u64));
+        idt.page_fault.set_handler_addr(VirtAddr::new(page_fault_handler as *const () as u64));
+        idt.invalid_opcode.set_handler_addr(VirtAddr::new(invalid_opcode as *const () as u64));
         idt.break_point.set_handler_fn(break_point);
         idt.double_fault.set_handler_fn(double_fault_handler).set_stack_index(DOUBLE_FAULT_IST_INDEX);
     }
@@ -357,7 +360,7 @@ fn eoi() {
 
 #[unsafe(no_mangle)]
 extern "C" fn external_interrupt_handler(stack_frame: &mut ExtendedInterruptStackFrame, idx: u8) {
-    let from_user = SegmentSelector(stack_frame.code_segment as u16).privilege_level() == PrivilegeLevel::Ring3;
+    let from_user = raised_by_user(stack_frame);
     if from_user {
         unsafe { GsBase::swap() };
     }
@@ -390,18 +393,45 @@ extern "C" fn external_interrupt_handler(stack_frame: &mut ExtendedInterruptStac
         return eoi();
     }
 
-    *IS_IN_ISR.inner_mut() = true;
     *LAST_INTERRUPT_NO.inner_mut() = idx.as_u8();
+    eoi();
+
+    handle_isr_request(stack_frame, RequestReferer::HardwareInterrupt(idx), from_user);
+}
+
+fn raised_by_user(stack_frame: &ExtendedInterruptStackFrame) -> bool {
+    SegmentSelector(stack_frame.code_segment as u16).privilege_level() == PrivilegeLevel::Ring3
+}
+
+/// Route a fault raised in ring 3 through the pipeline, the user gs base must still be active
+fn handle_user_fault(stack_frame: &mut ExtendedInterruptStackFrame, fault: UserFault) {
+    unsafe { GsBase::swap() };
+
+    if PANIC_COUNT.load(Ordering::SeqCst) > 0 {
+        hlt_loop();
+    }
+
+    debug_assert!(cpu_local_avaiable());
+    debug_assert!(!*IS_IN_SYSCALL, "IS_IN_SYSCALL is set when code segment is ring 3");
+    debug_assert!(!*IS_IN_ISR, "IS_IN_ISR is set when code segment is ring 3");
+
+    handle_isr_request(stack_frame, RequestReferer::UserFault(fault), true);
+}
+
+/// Handle a request coming from an interrupt gate, then apply the dispatched state to the stack frame
+///
+/// Must be called with the kernel gs base active, and interrupts disabled.
+fn handle_isr_request(stack_frame: &mut ExtendedInterruptStackFrame, referer: RequestReferer, from_user: bool) {
+    *IS_IN_ISR.inner_mut() = true;
 
     // this is safe now since IS_IN_ISR is set, the interrupt will be queued
     enable();
-    eoi();
 
     let mut c_stack_frame = CommonRequestStackFrame::from(&*stack_frame);
     let mut swap_to_user_gs = from_user;
 
     pipeline::handle_request(
-        CommonRequestContext::new(&mut c_stack_frame, RequestReferer::HardwareInterrupt(idx)),
+        CommonRequestContext::new(&mut c_stack_frame, referer),
         |CommonRequestContext { stack_frame: c_stack_frame, .. }, dispatcher| {
             dispatcher.dispatch(|action| match action {
                 DispatchAction::HltLoop => {
@@ -462,7 +492,7 @@ macro_rules! handler {
             #[unsafe(naked)]
             $vis extern "C" fn $fn_name() {
                 #[unsafe(no_mangle)]
-                fn [<handler_ $fn_name>]($stack_frame_name: $stack_frame_ty) {
+                extern "C" fn [<handler_ $fn_name>]($stack_frame_name: $stack_frame_ty) {
                     $($body)*
                 }
 
@@ -507,15 +537,81 @@ macro_rules! handler {
     };
 }
 
+/// Same as [`handler!`] but for the exceptions pushing an error code, the error code slot is swapped
+/// with `rax` so the stack layout ends up being the same as [`ExtendedInterruptStackFrame`]
+macro_rules! handler_with_error_code {
+    ($vis:vis fn $fn_name: ident($stack_frame_name: ident : $stack_frame_ty: ty, $error_code_name: ident : u64) { $($body:tt)* }) => {
+        paste::paste! {
+            #[unsafe(no_mangle)]
+            #[unsafe(naked)]
+            $vis extern "C" fn $fn_name() {
+                #[unsafe(no_mangle)]
+                extern "C" fn [<handler_ $fn_name>]($stack_frame_name: $stack_frame_ty, $error_code_name: u64) {
+                    $($body)*
+                }
+
+                core::arch::naked_asm!(
+                    // Save rax in place of the error code, and get the error code in rax
+                    "xchg [rsp], rax",
+                    "push rbx",
+                    "push rcx",
+                    "push rdx",
+                    "push rbp",
+                    "push rdi",
+                    "push rsi",
+                    "push r8",
+                    "push r9",
+                    "push r10",
+                    "push r11",
+                    "push r12",
+                    "push r13",
+                    "push r14",
+                    "push r15",
+                    "mov rdi, rsp",
+                    "mov rsi, rax",
+                    concat!("call ", stringify!([<handler_ $fn_name>])),
+                    "pop r15",
+                    "pop r14",
+                    "pop r13",
+                    "pop r12",
+                    "pop r11",
+                    "pop r10",
+                    "pop r9",
+                    "pop r8",
+                    "pop rsi",
+                    "pop rdi",
+                    "pop rbp",
+                    "pop rdx",
+                    "pop rcx",
+                    "pop rbx",
+                    "pop rax",
+                    // Return from interrupt
+                    "iretq",
+                );
+            }
+        }
+    };
+}
+
 handler!(
-    fn invalid_opcode(stack_frame: ExtendedInterruptStackFrame) {
+    fn invalid_opcode(stack_frame: &mut ExtendedInterruptStackFrame) {
+        if raised_by_user(stack_frame) {
+            return handle_user_fault(stack_frame, UserFault::InvalidOpcode);
+        }
+
         panic!("EXCEPTION: INVALID OPCODE\n{:#?}", stack_frame);
     }
 );
 
-extern "x86-interrupt" fn general_protection_fault_handler(stack_frame: InterruptStackFrame, error_code: u64) {
-    panic!("EXCEPTION: GENERAL PROTECTION FAULT\n{:#?}, ERROR_CODE: {}", stack_frame, error_code);
-}
+handler_with_error_code!(
+    fn general_protection_fault_handler(stack_frame: &mut ExtendedInterruptStackFrame, error_code: u64) {
+        if raised_by_user(stack_frame) {
+            return handle_user_fault(stack_frame, UserFault::GeneralProtection { error_code });
+        }
+
+        panic!("EXCEPTION: GENERAL PROTECTION FAULT\n{:#?}, ERROR_CODE: {}", stack_frame, error_code);
+    }
+);
 
 extern "x86-interrupt" fn break_point(_stack_frame: InterruptStackFrame) {}
 
@@ -523,10 +619,21 @@ extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame,
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}, ERROR_CODE: {}", stack_frame, error_code);
 }
 
-extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
-    log!(Critical, "EXCEPTION: PAGE FAULT");
-    log!(Critical, "Accessed Address: {:x?}", Cr2::read());
-    log!(Critical, "Error Code: {:?}", error_code);
-    log!(Critical, "{:#?}", stack_frame);
-    panic!("PAGE FAULT");
-}
+handler_with_error_code!(
+    fn page_fault_handler(stack_frame: &mut ExtendedInterruptStackFrame, error_code: u64) {
+        let error_code = PageFaultErrorCode::from_bits_truncate(error_code);
+        if user_memory::recover_page_fault(stack_frame, error_code) {
+            return;
+        }
+
+        if raised_by_user(stack_frame) {
+            return handle_user_fault(stack_frame, UserFault::PageFault { address: Cr2::read().addr(), error_code });
+        }
+
+        log!(Critical, "EXCEPTION: PAGE FAULT");
+        log!(Critical, "Accessed Address: {:x?}", Cr2::read());
+        log!(Critical, "Error Code: {:?}", error_code);
+        log!(Critical, "{:#?}", stack_frame);
+        panic!("PAGE FAULT");
+    }
+);
@@ -14,6 +14,7 @@ use crate::{
     interrupt::TPMS,
     memory::{MMIOBuffer, MMIOBufferInfo, MMIODevice},
     smp::{APIC_ID_TO_CPU_ID, CoreId, core_id_to_apic_id},
+    time,
 };
 
 use super::InterruptIndex;
@@ -126,7 +127,11 @@ impl LocalApic {
 
         log!(Debug, "APIC Timer Count Before PIT 10 ms: {}", self.current_count());
 
+        // The TSC is calibrated along, since it's waiting for the PIT anyway
+        let tsc_start = time::rdtsc();
         PIT.get().unwrap().lock().dumb_wait_10ms();
+        let tsc_end = time::rdtsc();
+        time::calibrate_tsc(tsc_start, tsc_end, 10);
 
         log!(Debug, "APIC Timer Count After PIT 10 ms: {}", self.current_count());
         let ticks_per_ms = (initial_count - self.current_count()) / 10;
@@ -138,7 +138,9 @@ pub struct Idt {
     // Vector nr 5
     pub bound_range_exceeded: Gate<NormalHandler, GateTrap>,
     // Vector nr 6
-    pub invalid_opcode: Gate<NormalHandler, GateTrap>,
+    // Faults that can come from userland are interrupt gates, so nothing can interrupt the handler
+    // before it swaps the gs base
+    pub invalid_opcode: Gate<NormalHandler, GateInterrupt>,
     // Vector nr 7
     pub device_not_available: Gate<NormalHandler, GateTrap>,
     // Vector nr 8
@@ -152,9 +154,9 @@ pub struct Idt {
     // Vector nr 12
     pub stack_segment_fault: Gate<HandlerWithErrorCode, GateTrap>,
     // Vector nr 13
-    pub general_protection: Gate<HandlerWithErrorCode, GateTrap>,
+    pub general_protection: Gate<HandlerWithErrorCode, GateInterrupt>,
     // Vector nr 14
-    pub page_fault: Gate<PageFaultHandler, GateTrap>,
+    pub page_fault: Gate<PageFaultHandler, GateInterrupt>,
     // Vector nr 15
     _intel_reserved: Gate<ReservedGate, GateTrap>,
     // Vector nr 16
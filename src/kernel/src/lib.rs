@@ -40,6 +40,7 @@ pub mod port;
 pub mod print;
 pub mod serial;
 pub mod syscall;
+pub mod time;
 pub mod userland;
 pub mod utils;
 
@@ -102,6 +103,7 @@ pub fn init(boot_bridge: *mut RawBootBridge) -> ! {
     memory::init_local(&mut stage4);
     userland::init(&mut stage4);
     pit::init(&mut stage4);
+    time::init(&mut stage4);
     syscall::init(&mut stage4);
     LOGGER.flush_all(&[|s| serial_print!("{s}"), |s| print!("{s}")]);
     smp::init_aps(stage4);
@@ -55,6 +55,16 @@ impl<'a, A: FrameAllocator, R: RootLevel> WithMapper<'a, StackAllocator, A, R> {
     pub fn alloc_stack_kernel(&mut self) -> Option<stack_allocator::Stack> {
         self.inner.alloc_stack_kernel(self.mapper.mapper, self.mapper.allocator)
     }
+
+    /// Give a stack allocated with [`Self::alloc_stack_kernel`] back
+    ///
+    /// # Safety
+    /// Nothing may reference the stack anymore, and since the upper half is shared by every core none
+    /// of the other cores may have its pages cached in its TLB
+    pub unsafe fn dealloc_stack_kernel(&mut self, stack: stack_allocator::Stack) {
+        // SAFETY: The contract is uphold by the caller
+        unsafe { self.inner.dealloc_stack(self.mapper.mapper, self.mapper.allocator, stack) };
+    }
 }
 
 def_local!(pub static ACTIVE_TABLE_UPPER: Arc<Mutex<ActivePageTable<RootRecurseUpperHalf>>>);
@@ -240,6 +250,7 @@ pub fn init(mut ctx: InitializationContext<Stage0>) -> InitializationContext<Sta
                 Page::range_inclusive(stack_alloc_start, VirtAddr::new(stack_alloc_end).into())
             },
             false,
+            false,
         ),
         TemporaryTable::new(),
     ));
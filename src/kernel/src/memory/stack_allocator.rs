@@ -1,21 +1,35 @@
+use alloc::vec::Vec;
 use config::config;
 use pager::{
-    EntryFlags, PAGE_SIZE,
-    address::{Page, PageIter, Size4K, VirtAddr},
+    EntryFlags, KERNEL_DIRECT_PHYSICAL_MAP, PAGE_SIZE,
+    address::{AnyFrame, Frame, Page, PageIter, Size4K, VirtAddr},
     allocator::FrameAllocator,
     paging::{mapper::Mapper, table::RootLevel},
 };
 
+/// Allocates stacks in a page range, every stack is preceded by an unmapped guard page. The slot
+/// (the guard page and the stack pages) of a deallocated stack is reused by the next stack of the
+/// same size
 pub struct StackAllocator {
     range: PageIter<Size4K>,
     original_range: PageIter<Size4K>,
+    free_slots: Vec<StackSlot>,
     ua: bool,
+    zeroed: bool,
+}
+
+/// The pages of a deallocated stack, the guard page below them stays unmapped
+#[derive(Debug, Clone, Copy)]
+struct StackSlot {
+    start: Page<Size4K>,
+    size_in_pages: usize,
 }
 
 impl StackAllocator {
-    /// Create a new stack allocator
-    pub fn new(page_range: PageIter<Size4K>, ua: bool) -> StackAllocator {
-        StackAllocator { range: page_range.clone(), original_range: page_range, ua }
+    /// Create a new stack allocator, the stacks are user accessible if `ua` is set, and are zeroed
+    /// before being handed out if `zeroed` is set
+    pub fn new(page_range: PageIter<Size4K>, ua: bool, zeroed: bool) -> StackAllocator {
+        StackAllocator { range: page_range.clone(), original_range: page_range, free_slots: Vec::new(), ua, zeroed }
     }
 
     pub fn original_range(&self) -> PageIter<Size4K> {
@@ -40,39 +54,105 @@ impl StackAllocator {
             return None;
         }
 
-        let mut range = self.range.clone();
+        let (start, end) = match self.free_slots.iter().position(|slot| slot.size_in_pages == size_in_pages) {
+            Some(index) => {
+                let slot = self.free_slots.swap_remove(index);
+                let end = Page::range(slot.start, size_in_pages as u64).last()?;
+                (slot.start, end)
+            }
+            None => {
+                let mut range = self.range.clone();
 
-        let guard_page = range.next();
-        let stack_start = range.next();
-        let stack_end = if size_in_pages == 1 { stack_start } else { range.nth(size_in_pages - 2) };
+                let guard_page = range.next();
+                let stack_start = range.next();
+                let stack_end = if size_in_pages == 1 { stack_start } else { range.nth(size_in_pages - 2) };
 
-        match (guard_page, stack_start, stack_end) {
-            (Some(_guard), Some(start), Some(end)) => {
+                let (Some(_guard), Some(start), Some(end)) = (guard_page, stack_start, stack_end) else {
+                    return None;
+                };
                 self.range = range;
+                (start, end)
+            }
+        };
+
+        for page in Page::range_inclusive(start, end) {
+            mapper.map(
+                page,
+                EntryFlags::WRITABLE
+                    | EntryFlags::NO_EXECUTE
+                    | if self.ua { EntryFlags::USER_ACCESSIBLE } else { EntryFlags::empty() },
+                frame_allocator,
+            );
+
+            if self.zeroed
+                && let Some(AnyFrame::Frame4K(frame)) = mapper.translate_page(page)
+            {
+                zero_frame(frame);
+            }
+        }
+
+        let top_of_stack = end.start_address().as_u64() + PAGE_SIZE;
+        log!(
+            Trace,
+            "Allocated stack, size: {size:#x}, top: {top:#x}, bottom: {bottom:#x}",
+            bottom = start.start_address().as_u64(),
+            top = top_of_stack,
+            size = size_in_pages as u64 * PAGE_SIZE,
+        );
+        // SAFETY: We've already mapped the stack above as writeable and non executeable
+        Some(unsafe { Stack::new(VirtAddr::new(top_of_stack), start.start_address()) })
+    }
+
+    /// Unmap the stack and free its frames, the pages that aren't mapped anymore (e.g. the address
+    /// space was torn down) are skipped
+    ///
+    /// # Safety
+    /// The stack must have been allocated by this allocator in the page table of `mapper`, and
+    /// nothing may reference it anymore
+    pub unsafe fn dealloc_stack<Root: RootLevel, A: FrameAllocator>(
+        &mut self,
+        mapper: &mut Mapper<Root>,
+        frame_allocator: &mut A,
+        stack: Stack,
+    ) {
+        let start = Page::containing_address(stack.bottom());
+        let size_in_pages = ((stack.top().as_u64() - stack.bottom().as_u64()) / PAGE_SIZE) as usize;
 
-                for page in Page::range_inclusive(start, end) {
-                    mapper.map(
-                        page,
-                        EntryFlags::WRITABLE
-                            | EntryFlags::NO_EXECUTE
-                            | if self.ua { EntryFlags::USER_ACCESSIBLE } else { EntryFlags::empty() },
-                        frame_allocator,
-                    );
-                }
-
-                let top_of_stack = end.start_address().as_u64() + PAGE_SIZE;
-                log!(
-                    Trace,
-                    "Allocated stack, size: {size:#x}, top: {top:#x}, bottom: {bottom:#x}",
-                    bottom = start.start_address().as_u64(),
-                    top = top_of_stack,
-                    size = size_in_pages as u64 * PAGE_SIZE,
-                );
-                // SAFETY: We've already mapped the stack above as writeable and non executeable
-                Some(unsafe { Stack::new(VirtAddr::new(top_of_stack), start.start_address()) })
+        for page in Page::range(start, size_in_pages as u64) {
+            if mapper.translate_page(page).is_some() {
+                // SAFETY: The page belongs to the stack, which isn't referenced anymore
+                unsafe { mapper.unmap(page, frame_allocator) };
             }
-            _ => None,
         }
+
+        self.free_slots.push(StackSlot { start, size_in_pages });
+    }
+
+    /// Zero the stack in place, for a stack handed to a new owner without going through
+    /// [`Self::dealloc_stack`]. Does nothing if the allocator doesn't zero its stacks
+    pub fn zero_stack<Root: RootLevel>(&self, mapper: &Mapper<Root>, stack: &Stack) {
+        if !self.zeroed {
+            return;
+        }
+
+        let start = Page::<Size4K>::containing_address(stack.bottom());
+        let end = Page::containing_address(stack.top() - 1usize);
+        for page in Page::range_inclusive(start, end) {
+            if let Some(AnyFrame::Frame4K(frame)) = mapper.translate_page(page) {
+                zero_frame(frame);
+            }
+        }
+    }
+}
+
+/// Zero a stack frame through the direct physical map, since the page mapping it might belong to an
+/// inactive page table
+fn zero_frame(frame: Frame<Size4K>) {
+    // SAFETY: Every frame is mapped in the direct physical map, and the frame only backs a stack that
+    // isn't in use
+    unsafe {
+        let address = KERNEL_DIRECT_PHYSICAL_MAP + frame.start_address().as_u64();
+        core::ptr::write_bytes(address.as_mut_ptr::<u8>(), 0, PAGE_SIZE as usize);
     }
 }
 
@@ -3,7 +3,7 @@ use core::arch::{asm, naked_asm};
 use kernel_proc::{def_local, local_builder};
 use pager::{
     address::VirtAddr,
-    registers::{Efer, SystemCallLStar, SystemCallStar},
+    registers::{Efer, RFlags, SystemCallFMask, SystemCallLStar, SystemCallStar},
 };
 
 use crate::{
@@ -39,6 +39,11 @@ pub fn init(ctx: &mut InitializationContext<Stage4>) {
                 Efer::SystemCallExtensions.write_retained();
                 SystemCallStar { syscall_selector: *KERNEL_CODE_SEG, sysret_selector: *USER_CODE_SEG_DUMMY }.write();
                 SystemCallLStar::write(VirtAddr::new(syscall_entry as *const () as u64));
+                // The user controls these flags, the kernel code expects them cleared (e.g. a set direction
+                // flag reverses rep movsb)
+                SystemCallFMask::write(
+                    RFlags::Direction | RFlags::InterruptEnable | RFlags::Trap | RFlags::AlignmentCheck,
+                );
             }
         });
         l.register(|builder, _context, _id| {
@@ -53,7 +58,7 @@ extern "C" fn syscall_handler(stack_frame: &mut CommonRequestStackFrame) {
     interrupt::enable();
     debug_assert!(is_stack_aligned_16(), "Unaligned stack in syscall handler");
 
-    let id = SyscallId(stack_frame.rax as u32);
+    let id = SyscallId(stack_frame.rax);
     let mut should_hlt = false;
     let mut use_iret = false;
     userland::pipeline::handle_request(
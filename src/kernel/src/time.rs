@@ -0,0 +1,135 @@
+//! The kernel clocks, a monotonic clock counting from the boot and a wall clock seeded from the
+//! UEFI runtime at boot.
+//!
+//! The monotonic clock is read from the TSC when it's invariant (it then ticks at a constant rate,
+//! synchronized between the cores), its frequency is calibrated against the PIT along the local APIC
+//! timer (See [`calibrate_tsc`]). Otherwise the caller falls back to the timer count of its core,
+//! which only has the resolution of a timer tick, and is clamped to the latest time read on any core
+//! since the cores don't count in lockstep (See [`monotonic_nanos`]).
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use raw_cpuid::CpuId;
+
+use crate::{
+    driver::uefi_runtime::{self, Time},
+    initialization_context::{InitializationContext, Stage4},
+    initialize_guard,
+};
+
+pub const NANOS_PER_MILLI: u64 = 1_000_000;
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+static INVARIANT_TSC: AtomicBool = AtomicBool::new(false);
+static TSC_PER_MS: AtomicU64 = AtomicU64::new(0);
+/// The TSC value the monotonic clock starts at
+static BOOT_TSC: AtomicU64 = AtomicU64::new(0);
+/// The wall clock time when the monotonic clock started, in nanoseconds since the unix epoch
+static BOOT_WALL_CLOCK: AtomicU64 = AtomicU64::new(0);
+/// The latest monotonic time read from the timer count of a core, when the TSC isn't usable
+static LATEST_TIMER_NANOS: AtomicU64 = AtomicU64::new(0);
+
+pub fn init(ctx: &mut InitializationContext<Stage4>) {
+    initialize_guard!();
+
+    let invariant = CpuId::new().get_advanced_power_mgmt_info().is_some_and(|info| info.has_invariant_tsc());
+    INVARIANT_TSC.store(invariant, Ordering::Relaxed);
+    log!(Debug, "Invariant TSC: {invariant}");
+
+    ctx.local_initializer(|i| {
+        // The UEFI runtime maps its memory through the local mapper
+        i.after_bsp(|| {
+            uefi_runtime::init();
+            let wall_clock = match uefi_runtime::uefi_runtime().lock().get_time() {
+                Ok(time) => unix_nanos(&time),
+                Err(status) => {
+                    log!(Warning, "Failed to read the UEFI time {status:?}, the wall clock starts at the epoch");
+                    0
+                }
+            };
+
+            BOOT_WALL_CLOCK.store(wall_clock, Ordering::Relaxed);
+            BOOT_TSC.store(rdtsc(), Ordering::Relaxed);
+        });
+    });
+}
+
+/// Record the TSC frequency, `start` and `end` are the TSC values read around a `millis` wait
+pub fn calibrate_tsc(start: u64, end: u64, millis: u64) {
+    let tsc_per_ms = end.saturating_sub(start) / millis;
+    TSC_PER_MS.store(tsc_per_ms, Ordering::Relaxed);
+}
+
+/// The TSC ticks per millisecond, [`None`] if the TSC isn't usable as a clock
+pub fn tsc_per_ms() -> Option<u64> {
+    let tsc_per_ms = TSC_PER_MS.load(Ordering::Relaxed);
+    (INVARIANT_TSC.load(Ordering::Relaxed) && tsc_per_ms != 0).then_some(tsc_per_ms)
+}
+
+/// The monotonic time in nanoseconds read from the TSC, [`None`] if the TSC isn't usable as a clock
+pub fn tsc_nanos() -> Option<u64> {
+    let tsc_per_ms = tsc_per_ms()?;
+    let elapsed = rdtsc().saturating_sub(BOOT_TSC.load(Ordering::Relaxed));
+    Some((elapsed as u128 * NANOS_PER_MILLI as u128 / tsc_per_ms as u128) as u64)
+}
+
+/// The monotonic time in nanoseconds, `timer_nanos` is the timer count of the calling core used when
+/// the TSC isn't usable. A thread migrating to a core whose count is behind never sees the time go
+/// backward, the clock stalls until that core catches up instead
+pub fn monotonic_nanos(timer_nanos: u64) -> u64 {
+    tsc_nanos().unwrap_or_else(|| LATEST_TIMER_NANOS.fetch_max(timer_nanos, Ordering::Relaxed).max(timer_nanos))
+}
+
+/// The wall clock time in nanoseconds since the unix epoch, given the `monotonic` time
+pub fn wall_clock_nanos(monotonic: u64) -> u64 {
+    BOOT_WALL_CLOCK.load(Ordering::Relaxed).saturating_add(monotonic)
+}
+
+pub fn rdtsc() -> u64 {
+    // SAFETY: rdtsc has no side effects
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Convert an UEFI time into nanoseconds since the unix epoch, times before the epoch are clamped
+fn unix_nanos(time: &Time) -> u64 {
+    let days = days_from_civil(time.year as i64, time.month as i64, time.day as i64);
+    let mut seconds = days * SECONDS_PER_DAY + time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64;
+
+    // The UEFI time is the local time, `time_zone` minutes ahead of UTC
+    if time.time_zone != Time::UNSPECIFIED_TIMEZONE {
+        seconds -= time.time_zone as i64 * 60;
+    }
+
+    u64::try_from(seconds).map_or(0, |seconds| seconds * NANOS_PER_SECOND + time.nanosecond as u64)
+}
+
+/// The number of days between the unix epoch and a date of the proleptic gregorian calendar, see
+/// <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn unix_epoch_conversion() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+
+        let time = Time { year: 2024, month: 2, day: 29, hour: 12, minute: 30, second: 15, ..Default::default() };
+        let expected = 1_709_209_815 * NANOS_PER_SECOND;
+        assert_eq!(unix_nanos(&Time { time_zone: Time::UNSPECIFIED_TIMEZONE, ..time }), expected);
+        assert_eq!(unix_nanos(&Time { time_zone: 60, ..time }), expected - 3600 * NANOS_PER_SECOND);
+    }
+}
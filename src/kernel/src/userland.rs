@@ -9,10 +9,15 @@ use crate::{
 
 pub const STACK_START: VirtAddr = VirtAddr::new(0x0000_7FFF_0000_0000);
 pub const STACK_MAX_SIZE: usize = 0xFFFF_FFFF; // 4 GIB Overall stack per process is probably enough.
+/// Where the anonymous memory mappings are placed when the process doesn't ask for a fixed address
+pub const MMAP_START: VirtAddr = VirtAddr::new(0x0000_1000_0000_0000);
 
+pub mod fault;
+pub mod initial_stack;
 pub mod ipp;
 pub mod pipeline;
 pub mod syscall;
+pub mod user_memory;
 
 static PACKED_DATA: OnceCell<Packed<'static>> = OnceCell::uninit();
 
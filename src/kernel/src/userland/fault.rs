@@ -0,0 +1,56 @@
+//! Faults raised by a user thread (e.g. a null dereference), they're routed through the pipeline as a
+//! request instead of panicking the kernel, and kill the faulting process.
+
+use core::fmt;
+
+use hotline::KILLED_EXIT_CODE;
+use pager::address::VirtAddr;
+
+use crate::{
+    interrupt::idt::PageFaultErrorCode,
+    userland::pipeline::{CommonRequestContext, ControlPipeline, PipelineContext, TaskBlock},
+};
+
+/// A cpu exception raised while running in ring 3
+#[derive(Debug, Clone, Copy)]
+pub enum UserFault {
+    PageFault { address: VirtAddr, error_code: PageFaultErrorCode },
+    GeneralProtection { error_code: u64 },
+    InvalidOpcode,
+}
+
+impl fmt::Display for UserFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserFault::PageFault { address, error_code } => {
+                write!(f, "page fault accessing {:#x} ({error_code:?})", address.as_u64())
+            }
+            UserFault::GeneralProtection { error_code } => {
+                write!(f, "general protection fault (error code {error_code})")
+            }
+            UserFault::InvalidOpcode => write!(f, "invalid opcode"),
+        }
+    }
+}
+
+pub(super) fn fault_handle(
+    rq_context: &CommonRequestContext,
+    pipeline: &mut ControlPipeline,
+    pipeline_context: &mut PipelineContext,
+    fault: UserFault,
+) {
+    // An invalid task is already dying, it won't be scheduled again
+    let Some(faulting_task) = pipeline_context.interrupted_task.filter(TaskBlock::valid) else {
+        return;
+    };
+
+    log!(
+        Error,
+        "Thread {} {fault} at rip {:#x}, killing its process",
+        faulting_task.thread.id(),
+        rq_context.stack_frame.instruction_pointer.as_u64()
+    );
+
+    pipeline.free_thread(pipeline_context, faulting_task.thread);
+    pipeline.exit_process(faulting_task.process, KILLED_EXIT_CODE);
+}
@@ -0,0 +1,160 @@
+//! The initial stack of the main thread of a program, laid out the System V way so the program
+//! `_start` can find its arguments:
+//!
+//! ```text
+//! stack pointer -> argc
+//!                  argv[0], ..., argv[argc - 1], NULL
+//!                  envp[0], ..., NULL
+//!                  auxv (type, value) pairs, ending with AuxvType::Null
+//!                  padding
+//!                  16 random bytes
+//!                  argument and environment strings
+//! stack top     ->
+//! ```
+//!
+//! The stack pointer is 16 bytes aligned, as long as the stack top is.
+
+use alloc::{vec, vec::Vec};
+use core::arch::asm;
+
+use hotline::AuxvType;
+use pager::address::VirtAddr;
+use raw_cpuid::CpuId;
+
+const WORD_SIZE: usize = size_of::<u64>();
+const RANDOM_SIZE: usize = 16;
+
+/// The image of an initial stack, to be copied at [`InitialStack::stack_pointer`]
+#[derive(Debug)]
+pub struct InitialStack {
+    image: Vec<u8>,
+    stack_pointer: VirtAddr,
+}
+
+impl InitialStack {
+    /// Lay out the stack ending at `top`, the strings are NUL terminated when copied. The
+    /// [`AuxvType::Random`] and [`AuxvType::Null`] entries are added to `auxv`.
+    pub fn new(top: VirtAddr, args: &[&[u8]], env: &[&[u8]], auxv: &[(AuxvType, u64)]) -> Self {
+        let strings_size: usize = args.iter().chain(env).map(|string| string.len() + 1).sum();
+        let data_size = RANDOM_SIZE + strings_size;
+        let word_count = 1 + (args.len() + 1) + (env.len() + 1) + (auxv.len() + 2) * 2;
+        let size = (word_count * WORD_SIZE + data_size).next_multiple_of(16);
+
+        let stack_pointer = top - size;
+        let mut image = vec![0; size];
+
+        let mut cursor = size - data_size;
+        let random = stack_pointer + cursor;
+        for chunk in image[cursor..cursor + RANDOM_SIZE].chunks_exact_mut(WORD_SIZE) {
+            chunk.copy_from_slice(&random_u64().to_ne_bytes());
+        }
+        cursor += RANDOM_SIZE;
+
+        let mut push_string = |string: &[u8]| {
+            let address = stack_pointer + cursor;
+            image[cursor..cursor + string.len()].copy_from_slice(string);
+            cursor += string.len() + 1;
+            address.as_u64()
+        };
+
+        let mut words = Vec::with_capacity(word_count);
+        words.push(args.len() as u64);
+        words.extend(args.iter().map(|arg| push_string(arg)));
+        words.push(0);
+        words.extend(env.iter().map(|var| push_string(var)));
+        words.push(0);
+        for &(typ, value) in auxv.iter().chain(&[(AuxvType::Random, random.as_u64()), (AuxvType::Null, 0)]) {
+            words.extend([u64::from(typ), value]);
+        }
+
+        for (chunk, word) in image.chunks_exact_mut(WORD_SIZE).zip(words) {
+            chunk.copy_from_slice(&word.to_ne_bytes());
+        }
+
+        Self { image, stack_pointer }
+    }
+
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    pub fn stack_pointer(&self) -> VirtAddr {
+        self.stack_pointer
+    }
+}
+
+/// Random bytes for the program (e.g. stack protector canaries), uses `rdrand` when supported
+fn random_u64() -> u64 {
+    if CpuId::new().get_feature_info().is_some_and(|info| info.has_rdrand()) {
+        // rdrand can transiently fail when the entropy is exhausted
+        for _ in 0..10 {
+            let value: u64;
+            let success: u8;
+            // SAFETY: rdrand is supported, and only writes to the registers
+            unsafe { asm!("rdrand {}", "setc {}", out(reg) value, out(reg_byte) success, options(nomem, nostack)) };
+            if success == 1 {
+                return value;
+            }
+        }
+    }
+
+    // Not random at all, but at least it differs between programs (splitmix64)
+    // SAFETY: rdtsc has no side effects
+    let mut value = unsafe { core::arch::x86_64::_rdtsc() }.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(stack: &InitialStack, index: usize) -> u64 {
+        let offset = index * WORD_SIZE;
+        u64::from_ne_bytes(stack.image()[offset..offset + WORD_SIZE].try_into().unwrap())
+    }
+
+    fn string_at(stack: &InitialStack, address: u64) -> &[u8] {
+        let offset = (address - stack.stack_pointer().as_u64()) as usize;
+        let length = stack.image()[offset..].iter().position(|byte| *byte == 0).unwrap();
+        &stack.image()[offset..offset + length]
+    }
+
+    #[test_case]
+    fn initial_stack_layout() {
+        let top = VirtAddr::new(0x7000_0000);
+        let stack = InitialStack::new(top, &[b"init", b"-v"], &[b"A=B"], &[(AuxvType::PageSize, 4096)]);
+
+        assert_eq!(stack.stack_pointer().as_u64() % 16, 0);
+        assert_eq!(stack.stack_pointer() + stack.image().len(), top);
+
+        assert_eq!(word(&stack, 0), 2);
+        assert_eq!(string_at(&stack, word(&stack, 1)), b"init");
+        assert_eq!(string_at(&stack, word(&stack, 2)), b"-v");
+        assert_eq!(word(&stack, 3), 0);
+        assert_eq!(string_at(&stack, word(&stack, 4)), b"A=B");
+        assert_eq!(word(&stack, 5), 0);
+
+        assert_eq!(word(&stack, 6), u64::from(AuxvType::PageSize));
+        assert_eq!(word(&stack, 7), 4096);
+        assert_eq!(word(&stack, 8), u64::from(AuxvType::Random));
+        let random = word(&stack, 9) - stack.stack_pointer().as_u64();
+        assert!(random as usize + RANDOM_SIZE <= stack.image().len());
+        assert_eq!(word(&stack, 10), u64::from(AuxvType::Null));
+        assert_eq!(word(&stack, 11), 0);
+    }
+
+    #[test_case]
+    fn initial_stack_empty() {
+        let top = VirtAddr::new(0x7000_0000);
+        let stack = InitialStack::new(top, &[], &[], &[]);
+
+        assert_eq!(stack.stack_pointer().as_u64() % 16, 0);
+        assert_eq!(word(&stack, 0), 0);
+        assert_eq!(word(&stack, 1), 0);
+        assert_eq!(word(&stack, 2), 0);
+        assert_eq!(word(&stack, 3), u64::from(AuxvType::Random));
+        assert_eq!(word(&stack, 5), u64::from(AuxvType::Null));
+    }
+}
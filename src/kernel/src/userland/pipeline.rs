@@ -22,26 +22,41 @@
 //! [^rd]: referring to a request dependent state
 //! [^pipeline]: a request independent procedure managing different type of resources (e.g. thread resources, process resources, ..).
 
-use core::cell::RefCell;
+use core::{cell::RefCell, num::NonZeroUsize};
 
-use alloc::vec::Vec;
+use alloc::{sync::Arc, vec, vec::Vec};
+use hotline::{
+    ARGUMENT_COUNT, ARGUMENT_REGISTERS, ArgumentRegister, AuxvType, Clock, KILLED_EXIT_CODE, MESSAGE_SIZE, MessageInfo,
+    NO_CAPABILITY, ProcessInfo, Protection, Signal, SyscallError, ThreadInfo,
+};
 use kernel_proc::{def_local, local_builder};
-use pager::{address::VirtAddr, registers::RFlags};
+use pager::{PAGE_SIZE, address::VirtAddr, registers::RFlags};
 use santa::Elf;
 use smart_default::SmartDefault;
 
 use crate::{
     initialization_context::{InitializationContext, Stage4},
-    interrupt::{self, InterruptIndex},
+    interrupt::{self, CORE_ID, InterruptIndex},
+    time,
     userland::{
         PACKED_DATA,
+        fault::UserFault,
+        initial_stack::InitialStack,
         pipeline::{
             dispatch::Dispatcher,
-            process::{Process, ProcessPipeline},
-            scheduler::SchedulerPipeline,
-            thread::{Thread, ThreadPipeline},
+            process::{
+                Process, ProcessPipeline,
+                capability::Capability,
+                file::File,
+                pipe,
+                port::{Message, Port},
+                shared_memory::SharedMemory,
+                signal::{self, Delivery, Disposition},
+            },
+            scheduler::{SchedulerPipeline, Timeout},
+            thread::{Thread, ThreadPipeline, extended_state::ExtendedState},
         },
-        syscall::SyscallId,
+        syscall::{SyscallId, write_console},
     },
 };
 
@@ -125,7 +140,13 @@ pub struct PipelineContext {
     pub should_schedule: bool,
     pub should_hlt: bool,
     pub interrupted_slept: bool,
+    pub interrupted_blocked: bool,
     pub interrupted_freed: bool,
+    pub interrupted_yielded: bool,
+    /// The interrupted task executes its syscall again once it's resumed
+    pub interrupted_restart: bool,
+    /// The saved state of the interrupted task was replaced, the syscall result isn't written to it
+    pub interrupted_restored: bool,
     pub scheduled_task: Option<TaskBlock>,
 }
 
@@ -147,37 +168,284 @@ impl ControlPipeline {
         self.scheduler.timer_count()
     }
 
+    /// The current time of `clock` in nanoseconds, see [`time::monotonic_nanos`]
+    pub fn clock_time(&self, clock: Clock) -> u64 {
+        let monotonic = time::monotonic_nanos(self.timer_count() as u64 * time::NANOS_PER_MILLI);
+        match clock {
+            Clock::Monotonic => monotonic,
+            Clock::Realtime => time::wall_clock_nanos(monotonic),
+        }
+    }
+
+    pub fn process_info(&self, pid: usize) -> Result<ProcessInfo, SyscallError> {
+        self.process.info(pid)
+    }
+
+    pub fn thread_info(&self, pid: usize, thread_id: usize) -> Result<ThreadInfo, SyscallError> {
+        self.process.thread_info(pid, thread_id)
+    }
+
+    /// Set the nice value of `thread`, it keeps the levels it sunk below its base level
+    pub fn set_priority(&mut self, thread: Thread, nice: i8) -> Result<(), SyscallError> {
+        self.thread.scheduling_mut(thread).set_nice(nice)
+    }
+
+    /// Set the handler of `signal` for the process, returns the previous handler
+    pub fn signal_action(
+        &mut self,
+        process: Process,
+        signal: Signal,
+        handler: usize,
+        trampoline: usize,
+    ) -> Result<usize, SyscallError> {
+        let disposition = Disposition::new(handler, trampoline)?;
+        let previous = self.process.set_signal_disposition(process, signal, disposition)?;
+        Ok(previous.raw_handler())
+    }
+
+    /// Send `signal` to the process `pid`
+    pub fn kill(&mut self, pid: usize, signal: Signal) -> Result<(), SyscallError> {
+        let process = self.process.find(pid)?;
+        if self.process.send_signal(process, None, signal)? {
+            self.exit_process(process, KILLED_EXIT_CODE);
+        }
+
+        Ok(())
+    }
+
+    /// Send `signal` to the thread `thread_id` of `process`
+    pub fn thread_kill(&mut self, process: Process, thread_id: usize, signal: Signal) -> Result<(), SyscallError> {
+        let thread = NonZeroUsize::new(thread_id).ok_or(SyscallError::NotFound)?;
+        if self.process.send_signal(process, Some(thread), signal)? {
+            self.exit_process(process, KILLED_EXIT_CODE);
+        }
+
+        Ok(())
+    }
+
+    /// Restore the state the calling thread had before running a signal handler, saved at `frame`
+    pub fn signal_return(&mut self, context: &mut PipelineContext, frame: VirtAddr) -> Result<(), SyscallError> {
+        let task = context.interrupted_task.expect("signal return called with no interrupted task");
+        let mut bytes = vec![0; signal::frame_size()];
+        self.process.copy_from_user(task.process, &mut bytes, frame)?;
+        let extended_state = signal::restore(self.thread.task_processor_state_mut(task.thread), &bytes)?;
+        self.thread.set_extended_state_bytes(task.thread, extended_state);
+
+        context.interrupted_restored = true;
+        Ok(())
+    }
+
+    /// Make the interrupted task run the handler of its next pending signal once it returns to
+    /// userland, or terminate its process if the signal isn't handled anymore
+    fn deliver_signal(&mut self, context: &mut PipelineContext) {
+        let Some(task) = context.interrupted_task.filter(TaskBlock::valid) else {
+            return;
+        };
+
+        if context.interrupted_slept || context.interrupted_blocked || context.interrupted_freed {
+            return;
+        }
+
+        let (signal, handler, trampoline) = match self.process.take_signal(task) {
+            None => return,
+            Some(Delivery::Terminate) => {
+                self.exit_process(task.process, KILLED_EXIT_CODE);
+                return;
+            }
+            Some(Delivery::Handle { signal, handler, trampoline }) => (signal, handler, trampoline),
+        };
+
+        let extended_state = self.thread.extended_state_bytes(task.thread);
+        let state = self.thread.task_processor_state(task.thread);
+        let saved = signal::save(state, &extended_state);
+        let Some(frame) = signal::frame_address(state.stack_pointer)
+            .filter(|frame| self.process.copy_to_user(task.process, *frame, &saved).is_ok())
+        else {
+            log!(Error, "Thread {} can't save its state to handle {signal:?}, killing its process", task.thread.id());
+            self.exit_process(task.process, KILLED_EXIT_CODE);
+            return;
+        };
+
+        let state = self.thread.task_processor_state_mut(task.thread);
+        *state = TaskProcesserState {
+            rdi: signal as u64,
+            rsi: handler.as_u64(),
+            rdx: frame.as_u64(),
+            instruction_pointer: trampoline,
+            stack_pointer: frame,
+            extended_state: core::mem::take(&mut state.extended_state),
+            ..Default::default()
+        };
+    }
+
     fn spawn_init(&mut self) {
         log!(Info, "Spawning init");
-        let packed = PACKED_DATA.get().unwrap();
-        let init_program = packed.iter().find(|e| e.name == "init").expect("Can't find init!");
+        let init = self.load_program(None, "init", &[]).expect("Failed to spawn init");
+        self.scheduler.add_task(init);
+    }
 
-        let init_program = Elf::new(init_program.data).expect("Init is not a valid elf");
-        let process = self.alloc_process();
+    /// Load the program `name` from the packed programs into a new process, the program receives the
+    /// name followed by `args` as its arguments. Returns the new process, its main thread is scheduled
+    /// on this core.
+    pub fn spawn_process(
+        &mut self,
+        context: &mut PipelineContext,
+        parent: Process,
+        name: &str,
+        args: &[&[u8]],
+    ) -> Result<Process, SyscallError> {
+        let task = self.load_program(Some(parent), name, args)?;
+        context.added_tasks.push(task);
+        Ok(task.process)
+    }
+
+    /// Load the program `name` from the packed programs into a new process, returns its main thread
+    /// with the arguments and the auxiliary vector placed on its stack
+    fn load_program(&mut self, parent: Option<Process>, name: &str, args: &[&[u8]]) -> Result<TaskBlock, SyscallError> {
+        let packed = PACKED_DATA.get().expect("The packed programs are not initialized");
+        let program = packed.iter().find(|program| program.name == name).ok_or(SyscallError::NotFound)?;
+        let program = Elf::new(program.data).map_err(|_| SyscallError::InvalidExecutable)?;
+
+        let process = self.alloc_process(parent);
         let entry = self.process.mem_access(
             // SAFETY: The mem access uphold the contract
-            |_process, mapper, allocator| unsafe { init_program.load(mapper, true, allocator).entry() },
+            |_process, mapper, allocator| unsafe { program.load(mapper, true, allocator).entry() },
             process,
         );
 
-        log!(Debug, "Init program entry at 0x{entry:x}");
+        log!(Debug, "Program {name} entry at 0x{entry:x}");
+
+        let mut auxv = vec![
+            (AuxvType::PageSize, PAGE_SIZE),
+            (AuxvType::Entry, entry.as_u64()),
+            (AuxvType::ProgramHeaderSize, program.program_header_size() as u64),
+            (AuxvType::ProgramHeaderCount, program.program_header_count() as u64),
+        ];
+        if let Some(headers) = program.program_headers_address() {
+            auxv.push((AuxvType::ProgramHeaders, headers.as_u64()));
+        }
 
-        self.scheduler.add_task(self.thread.alloc(&mut self.process, process, entry));
+        let argv: Vec<&[u8]> = core::iter::once(name.as_bytes()).chain(args.iter().copied()).collect();
+
+        let task = self.thread.alloc(&mut self.process, process, entry);
+        let stack = InitialStack::new(self.thread.stack_top(task.thread), &argv, &[], &auxv);
+        self.process
+            .copy_to_user(process, stack.stack_pointer(), stack.image())
+            .expect("The initial stack doesn't fit in the stack of the main thread");
+        self.thread.set_stack_pointer(task.thread, stack.stack_pointer());
+
+        Ok(task)
     }
 
     pub fn sleep_interrupted(&mut self, context: &mut PipelineContext, millis: usize) {
         self.scheduler.sleep_interrupted(context, millis);
     }
 
+    pub fn yield_interrupted(&mut self, context: &mut PipelineContext) {
+        self.scheduler.yield_interrupted(context);
+    }
+
     pub fn free_thread(&mut self, context: &mut PipelineContext, thread: Thread) {
         self.thread.free(thread);
-        self.process.free_thread(thread);
+        for joiner in self.process.free_thread(thread) {
+            scheduler::wake(joiner, 0);
+        }
 
         context.interrupted_freed = true;
     }
 
-    pub fn free_process(&mut self, process: Process) {
-        self.process.free(process);
+    /// Block the calling thread until the thread `id` of the same process is freed, returns right away
+    /// if the thread already exited
+    pub fn join(&mut self, context: &mut PipelineContext, id: usize) -> Result<(), SyscallError> {
+        let task = context.interrupted_task.expect("join called with no interrupted task");
+        let id = NonZeroUsize::new(id).ok_or(SyscallError::InvalidArgument)?;
+
+        // Joining itself would block forever
+        if id == task.thread.id() {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        if self.process.join(task, id) {
+            self.scheduler.block_interrupted(context);
+        }
+
+        Ok(())
+    }
+
+    /// Block the calling thread on the futex at `address` as long as it holds `expected`, for at most
+    /// `timeout` milliseconds
+    pub fn futex_wait(
+        &mut self,
+        context: &mut PipelineContext,
+        address: VirtAddr,
+        expected: u32,
+        timeout: Option<usize>,
+    ) -> Result<(), SyscallError> {
+        let task = context.interrupted_task.expect("futex wait called with no interrupted task");
+        if !address.as_u64().is_multiple_of(align_of::<u32>() as u64) {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        if !self.process.futex_wait(task, address, expected)? {
+            return Err(SyscallError::WouldBlock);
+        }
+
+        self.scheduler.block_interrupted(context);
+        if let Some(millis) = timeout {
+            self.scheduler.add_sleep(task, millis, Timeout::Futex { address });
+        }
+        Ok(())
+    }
+
+    /// Wake up to `count` threads of `process` blocked on the futex at `address`, returns the number
+    /// of threads woken up
+    pub fn futex_wake(
+        &mut self,
+        context: &mut PipelineContext,
+        process: Process,
+        address: VirtAddr,
+        count: usize,
+    ) -> Result<usize, SyscallError> {
+        if !address.as_u64().is_multiple_of(align_of::<u32>() as u64) {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        let woken = self.process.futex_wake(process, address, count);
+        for task in woken.iter().copied() {
+            if task.thread.core() == *CORE_ID {
+                self.scheduler.cancel_sleep(task);
+                context.added_tasks.push(task);
+            } else {
+                // Resumed with the return value of its futex wait
+                scheduler::resume(task);
+            }
+        }
+
+        Ok(woken.len())
+    }
+
+    /// Terminate the process with the exit `code`, waking up the parent thread waiting for it
+    pub fn exit_process(&mut self, process: Process, code: i32) {
+        if let Some(waiter) = self.process.exit(process, code) {
+            scheduler::wake(waiter, process.pid() as u64);
+        }
+    }
+
+    /// Collect the exit code of a child of the calling process, see [`ProcessPipeline::wait`], the
+    /// calling thread is blocked if the child is still alive
+    pub fn wait(
+        &mut self,
+        context: &mut PipelineContext,
+        pid: Option<usize>,
+        status: Option<VirtAddr>,
+    ) -> Result<Option<usize>, SyscallError> {
+        let task = context.interrupted_task.expect("wait called with no interrupted task");
+        let collected = self.process.wait(task, pid, status)?;
+        if collected.is_none() {
+            self.scheduler.block_interrupted(context);
+        }
+
+        Ok(collected)
     }
 
     pub fn alloc_thread(
@@ -185,18 +453,227 @@ impl ControlPipeline {
         context: &mut PipelineContext,
         parent_process: Process,
         start: VirtAddr,
-    ) -> Option<TaskBlock> {
+    ) -> Result<TaskBlock, SyscallError> {
         if start.is_canonical_higher_half() {
-            return None;
+            return Err(SyscallError::BadAddress);
         }
 
         let task = self.thread.alloc(&mut self.process, parent_process, start);
         context.added_tasks.push(task);
-        Some(task)
+        Ok(task)
     }
 
-    pub fn alloc_process(&mut self) -> Process {
-        self.process.alloc()
+    pub fn map_memory(
+        &mut self,
+        process: Process,
+        address: Option<VirtAddr>,
+        length: u64,
+        protection: Protection,
+    ) -> Result<VirtAddr, SyscallError> {
+        self.process.map_memory(process, address, length, protection)
+    }
+
+    pub fn unmap_memory(&mut self, process: Process, address: VirtAddr, length: u64) -> Result<(), SyscallError> {
+        self.process.unmap_memory(process, address, length)
+    }
+
+    pub fn protect_memory(
+        &mut self,
+        process: Process,
+        address: VirtAddr,
+        length: u64,
+        protection: Protection,
+    ) -> Result<(), SyscallError> {
+        self.process.protect_memory(process, address, length, protection)
+    }
+
+    pub fn copy_from_user(
+        &mut self,
+        process: Process,
+        destination: &mut [u8],
+        source: VirtAddr,
+    ) -> Result<(), SyscallError> {
+        self.process.copy_from_user(process, destination, source)
+    }
+
+    pub fn copy_to_user(&mut self, process: Process, destination: VirtAddr, source: &[u8]) -> Result<(), SyscallError> {
+        self.process.copy_to_user(process, destination, source)
+    }
+
+    /// Write the encoded syscall result into the saved state of the calling thread, so it's preserved
+    /// when the thread is scheduled out (or migrated) before returning to userland. A restarted syscall
+    /// gets its number back instead
+    pub fn syscall_return(&mut self, context: &PipelineContext, id: SyscallId, value: u64) {
+        if context.interrupted_freed || context.interrupted_restored {
+            return;
+        }
+
+        if let Some(task) = context.interrupted_task.filter(TaskBlock::valid) {
+            if context.interrupted_restart {
+                self.thread.restart_syscall(task.thread, id.0);
+            } else {
+                self.thread.set_return_value(task.thread, value);
+            }
+        }
+    }
+
+    /// Block the calling thread until it's resumed with [`scheduler::resume`], the syscall is then
+    /// executed again
+    fn block_restart(&mut self, context: &mut PipelineContext) {
+        self.scheduler.block_interrupted(context);
+        context.interrupted_restart = true;
+    }
+
+    /// Read up to `length` bytes from the file `fd` of the calling process into its memory at `buffer`,
+    /// returns [`None`] if the calling thread got blocked
+    pub fn read_file(
+        &mut self,
+        context: &mut PipelineContext,
+        fd: usize,
+        buffer: VirtAddr,
+        length: usize,
+    ) -> Result<Option<usize>, SyscallError> {
+        let task = context.interrupted_task.expect("read called with no interrupted task");
+        let mut bytes = vec![0; length];
+
+        // The data read from a pipe is gone, so the buffer is checked first and kept mapped until the
+        // data is copied to it
+        process::pin_mappings(task.process, || {
+            self.process.validate_user_range(task.process, buffer, length, true)?;
+            let read = match self.process.file(task.process, fd)? {
+                File::Console => return Err(SyscallError::BadFileDescriptor),
+                File::Pipe(end) => end.read(task, &mut bytes)?,
+            };
+
+            let Some(read) = read else {
+                self.block_restart(context);
+                return Ok(None);
+            };
+
+            self.process.copy_to_user(task.process, buffer, &bytes[..read])?;
+            Ok(Some(read))
+        })
+    }
+
+    /// Write `source` to the file `fd` of the calling process, returns [`None`] if the calling thread
+    /// got blocked
+    pub fn write_file(
+        &mut self,
+        context: &mut PipelineContext,
+        fd: usize,
+        source: &[u8],
+    ) -> Result<Option<usize>, SyscallError> {
+        let task = context.interrupted_task.expect("write called with no interrupted task");
+        let written = match self.process.file(task.process, fd)? {
+            File::Console => {
+                write_console(source);
+                Some(source.len())
+            }
+            File::Pipe(end) => end.write(task, source)?,
+        };
+
+        if written.is_none() {
+            self.block_restart(context);
+        }
+
+        Ok(written)
+    }
+
+    /// Create a pipe in `process`, returns the read end and the write end file descriptors
+    pub fn pipe(&mut self, process: Process) -> Result<(usize, usize), SyscallError> {
+        let (read, write) = pipe::pipe();
+        let read = self.process.open_file(process, File::Pipe(Arc::new(read)))?;
+        match self.process.open_file(process, File::Pipe(Arc::new(write))) {
+            Ok(write) => Ok((read, write)),
+            Err(error) => {
+                self.process.close_file(process, read)?;
+                Err(error)
+            }
+        }
+    }
+
+    pub fn close_file(&mut self, process: Process, fd: usize) -> Result<(), SyscallError> {
+        self.process.close_file(process, fd)
+    }
+
+    pub fn dup_file(&mut self, process: Process, fd: usize) -> Result<usize, SyscallError> {
+        self.process.dup_file(process, fd)
+    }
+
+    /// Create a port in `process`, returns its receive capability
+    pub fn port_create(&mut self, process: Process) -> Result<usize, SyscallError> {
+        self.process.insert_capability(process, Capability::Port { port: Port::new(), receive: true })
+    }
+
+    /// Send `data` through the capability `port` of `process`, moving the pages of `grant` (address
+    /// and length) and transferring a copy of the capability `capability` along the message
+    pub fn port_send(
+        &mut self,
+        process: Process,
+        port: usize,
+        data: [u8; MESSAGE_SIZE],
+        grant: Option<(VirtAddr, u64)>,
+        capability: Option<usize>,
+    ) -> Result<(), SyscallError> {
+        let port = Arc::clone(self.process.capability(process, port)?.port()?);
+        let capability = capability.map(|handle| self.process.capability(process, handle)).transpose()?;
+        let capability = capability.as_ref().map(Capability::transferred);
+
+        port.send(|| {
+            let grant = grant.map(|(address, length)| self.process.take_grant(process, address, length)).transpose()?;
+            Ok(Message { data, sender: process.pid(), grant, capability })
+        })
+    }
+
+    /// Receive a message through the receive capability `port` of the calling process, returns
+    /// [`None`] if the calling thread got blocked
+    pub fn port_receive(
+        &mut self,
+        context: &mut PipelineContext,
+        port: usize,
+    ) -> Result<Option<([u8; MESSAGE_SIZE], MessageInfo)>, SyscallError> {
+        let task = context.interrupted_task.expect("port_receive called with no interrupted task");
+        let port = Arc::clone(self.process.capability(task.process, port)?.receive_port()?);
+
+        let Some(Message { data, sender, grant, capability }) = port.receive(task)? else {
+            self.block_restart(context);
+            return Ok(None);
+        };
+
+        let mut info = MessageInfo { sender, capability: NO_CAPABILITY, ..Default::default() };
+        if let Some(grant) = grant {
+            info.grant_length = grant.len() * PAGE_SIZE as usize;
+            info.grant = self.process.place_grant(task.process, grant)?.as_u64() as usize;
+        }
+        if let Some(capability) = capability {
+            info.capability = self.process.insert_capability(task.process, capability)?;
+        }
+
+        Ok(Some((data, info)))
+    }
+
+    /// Create a shared memory object of `length` bytes in `process`, returns its capability
+    pub fn shared_memory_create(&mut self, process: Process, length: u64) -> Result<usize, SyscallError> {
+        self.process.insert_capability(process, Capability::SharedMemory(SharedMemory::new(length)?))
+    }
+
+    pub fn shared_memory_map(
+        &mut self,
+        process: Process,
+        handle: usize,
+        address: Option<VirtAddr>,
+        protection: Protection,
+    ) -> Result<VirtAddr, SyscallError> {
+        let memory = Arc::clone(self.process.capability(process, handle)?.shared_memory()?);
+        self.process.map_shared_memory(process, memory, address, protection)
+    }
+
+    pub fn close_capability(&mut self, process: Process, handle: usize) -> Result<(), SyscallError> {
+        self.process.remove_capability(process, handle)
+    }
+
+    pub fn alloc_process(&mut self, parent: Option<Process>) -> Process {
+        self.process.alloc(parent)
     }
 
     fn create_context(&mut self, context: &CommonRequestContext<'_>) -> PipelineContext {
@@ -247,7 +724,7 @@ impl ControlPipeline {
             return;
         }
 
-        self.scheduler.schedule(&mut self.thread, context);
+        self.scheduler.schedule(&mut self.thread, &mut self.process, context);
     }
 }
 
@@ -280,15 +757,16 @@ pub fn handle_hw_interrupt(idx: InterruptIndex) {
 /// Handle the request with the provided [`CommonRequestContext`], returning a dispatcher
 /// [`Dispatcher`] that must be used to operate the right following actions.
 pub fn handle_request<'b>(
-    rq_context: CommonRequestContext<'b>,
+    mut rq_context: CommonRequestContext<'b>,
     dispatch: impl for<'a> FnOnce(CommonRequestContext<'b>, Dispatcher<'a>),
 ) {
     let mut pipeline = PIPELINE.borrow_mut();
     let mut context = pipeline.create_context(&rq_context);
     match rq_context.referer {
         RequestReferer::SyscallRequest(id) => {
-            super::syscall::syscall_handle(&rq_context, &mut pipeline, &mut context, id)
+            super::syscall::syscall_handle(&mut rq_context, &mut pipeline, &mut context, id)
         }
+        RequestReferer::UserFault(fault) => super::fault::fault_handle(&rq_context, &mut pipeline, &mut context, fault),
         RequestReferer::HardwareInterrupt(InterruptIndex::CheckIPP) if pipeline.should_check_ipp => {
             pipeline.should_check_ipp = false;
         }
@@ -299,6 +777,7 @@ pub fn handle_request<'b>(
     }
 
     pipeline.handle_ipp(&mut context);
+    pipeline.deliver_signal(&mut context);
     pipeline.schedule(&mut context);
     pipeline.finalize(&mut context);
     dispatch(rq_context, Dispatcher::new(context, &pipeline.thread))
@@ -336,6 +815,23 @@ impl CommonRequestStackFrame {
         Self::default()
     }
 
+    /// Read the value of a syscall argument register
+    pub fn argument(&self, register: ArgumentRegister) -> u64 {
+        match register {
+            ArgumentRegister::Rdx => self.rdx,
+            ArgumentRegister::Rsi => self.rsi,
+            ArgumentRegister::Rdi => self.rdi,
+            ArgumentRegister::R8 => self.r8,
+            ArgumentRegister::R9 => self.r9,
+            ArgumentRegister::R10 => self.r10,
+        }
+    }
+
+    /// Read every syscall argument registers, in [`ARGUMENT_REGISTERS`] order
+    pub fn arguments(&self) -> [u64; ARGUMENT_COUNT] {
+        ARGUMENT_REGISTERS.map(|register| self.argument(register))
+    }
+
     pub fn replace_with(&mut self, task: &TaskProcesserState) {
         self.r15 = task.r15;
         self.r14 = task.r14;
@@ -374,6 +870,7 @@ impl<'a> CommonRequestContext<'a> {
 pub enum RequestReferer {
     HardwareInterrupt(InterruptIndex),
     SyscallRequest(SyscallId),
+    UserFault(UserFault),
 }
 
 #[derive(Debug, Clone, SmartDefault, PartialEq, Eq)]
@@ -424,11 +921,7 @@ impl<'a> From<&CommonRequestContext<'a>> for TaskProcesserState {
             cpu_flags: context.stack_frame.cpu_flags,
             stack_pointer: context.stack_frame.stack_pointer,
             instruction_pointer: context.stack_frame.instruction_pointer,
-            extended_state: ExtendedState,
+            extended_state: ExtendedState::default(),
         }
     }
 }
-
-// TODO: Implement Extened States (XSAVE, https://www.felixcloutier.com/x86/xsave)
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
-pub struct ExtendedState;
@@ -1,18 +1,22 @@
 use core::{
     num::NonZeroUsize,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
 };
 
-use alloc::{sync::Arc, vec::Vec};
-use hashbrown::HashSet;
+use alloc::{collections::vec_deque::VecDeque, sync::Arc, vec::Vec};
+use hashbrown::HashMap;
+use hotline::{ProcessInfo, Protection, Signal, SyscallError, ThreadInfo};
 use kernel_proc::IPPacket;
 use pager::{
-    address::Page,
+    EntryFlags, PAGE_SIZE,
+    address::{AnyFrame, Frame, Page, Size4K, VirtAddr},
+    allocator::FrameAllocator,
     paging::{
         InactivePageCopyOption, InactivePageTable,
         mapper::{Mapper, MapperWithAllocator},
-        table::RootRecurseLowerHalf,
+        table::{RootLevelRecurse, RootRecurseLowerHalf},
     },
+    registers::tlb,
 };
 use spin::{Mutex, RwLock};
 
@@ -26,9 +30,34 @@ use crate::{
     userland::{
         self,
         pipeline::{Event, PipelineContext, TaskBlock, thread::Thread},
+        user_memory,
     },
 };
 
+use self::{
+    capability::{Capability, CapabilityTable},
+    family::{ChildExit, Family, Waiter, Zombie},
+    file::{File, FileTable},
+    port::Grant,
+    region::RegionTracker,
+    shared_memory::{SharedMappings, SharedMemory},
+    shootdown::{ActiveCores, DeferredFrames, ReleasedFrames},
+    signal::{Delivery, Disposition, SignalState},
+};
+
+pub mod capability;
+mod family;
+pub mod file;
+pub mod pipe;
+pub mod port;
+mod region;
+pub mod shared_memory;
+mod shootdown;
+pub mod signal;
+
+/// The size of the stack of every user thread
+const USER_STACK_PAGES: usize = 16;
+
 #[derive(Default)]
 pub struct ProcessPipeline {
     page_tables: Vec<Option<InactivePageTable<RootRecurseLowerHalf>>>,
@@ -61,6 +90,7 @@ impl ProcessPipeline {
                 self.page_table_swap(interrupted.process, scheduled.process);
             }
             (None, Some(TaskBlock { process, .. })) => {
+                shared(&process).active_cores.insert();
                 let with = self.page_tables[process.id].take().expect("Some one forgot to put back their page table");
 
                 assert!(self.hlt_page_table.is_none(), "HLT page table didn't get swapped");
@@ -68,31 +98,46 @@ impl ProcessPipeline {
                 self.hlt_page_table = Some(switch_lower_half(with));
             }
             (Some(TaskBlock { process, .. }), None)
-                if context.should_schedule || context.interrupted_slept || context.interrupted_freed =>
+                if context.should_schedule
+                    || context.interrupted_slept
+                    || context.interrupted_blocked
+                    || context.interrupted_freed =>
             {
                 let hlt_table = self.hlt_page_table.take().expect("HLT Page table stolen or uninitialized");
 
                 self.page_tables[process.id] = Some(switch_lower_half(hlt_table));
+                shared(&process).active_cores.remove();
             }
             _ => {}
         }
     }
 
     fn check_ipp(&mut self) {
-        ExpandSharedPacket::handle(|packet| {
-            // SAFETY: The mutable exclusivity of the page table is ensure by page_table_modification_lock
-            self.page_tables
-                .push(Some(unsafe { copy_mappings(InactivePageCopyOption::lower_half(), &packet.table_template) }));
+        PageTablePacket::handle(|PageTablePacket { id, change }| match change {
+            PageTableChange::Expand(template) => {
+                debug_assert_eq!(id, self.page_tables.len(), "Page table expanded out of order");
+                // SAFETY: The mutable exclusivity of the page table is ensure by page_table_modification_lock
+                self.page_tables.push(Some(unsafe { copy_mappings(InactivePageCopyOption::lower_half(), &template) }));
+            }
+            PageTableChange::Link(tables) => self.table_mapper(id, |_s, mapper, _| link_lower_half(mapper, &tables)),
+            // The tables are freed once every core dropped them
+            PageTableChange::Unlink(_tables) => {
+                self.table_mapper(id, |_s, mapper, _| unlink_lower_half(mapper));
+            }
         });
+
+        shootdown::handle();
     }
 
     pub fn page_table_swap(&mut self, from: Process, with: Process) {
         assert_ne!(from, with);
 
+        shared(&with).active_cores.insert();
         let with = self.page_tables[with.id].take().expect("Page table scheduled two times");
 
         assert!(self.page_tables[from.id].is_none(), "Page table scheduled two times");
         self.page_tables[from.id] = Some(switch_lower_half(with));
+        shared(&from).active_cores.remove();
     }
 
     pub fn mem_access<R>(
@@ -124,11 +169,20 @@ impl ProcessPipeline {
         let pg_mod = &shared(&process).page_table_modification_lock;
         let _pg_mod = pg_mod.lock();
 
-        if let Some(mut table) = self.page_tables[process.id].take() {
+        self.table_mapper(process.id, f)
+    }
+
+    /// Same as [`Self::mapper`] without locking the page table, for the slots no process can use
+    fn table_mapper<R>(
+        &mut self,
+        id: usize,
+        f: impl FnOnce(&mut Self, &mut Mapper<RootRecurseLowerHalf>, &mut BuddyAllocator) -> R,
+    ) -> R {
+        if let Some(mut table) = self.page_tables[id].take() {
             let r = unsafe {
                 mapper_lower_with(|MapperWithAllocator { mapper, allocator }| f(self, mapper, allocator), &mut table)
             };
-            self.page_tables[process.id] = Some(table);
+            self.page_tables[id] = Some(table);
             r
         } else {
             // If the table is currently active, use that as a mapper
@@ -137,33 +191,631 @@ impl ProcessPipeline {
     }
 
     pub fn alloc_stack(&mut self, process: Process) -> Stack {
-        self.mapper(|_s, mapper, allocator| shared(&process).stacks.lock().alloc_stack(mapper, allocator, 16), process)
-            .expect("Can't allocate new stack for process, uhh deal with this, maybe kill the user process")
+        let shared = shared(&process);
+        let stack = self
+            .mapper(
+                |_s, mapper, allocator| shared.stacks.lock().alloc_stack(mapper, allocator, USER_STACK_PAGES),
+                process,
+            )
+            .expect("Can't allocate new stack for process, uhh deal with this, maybe kill the user process");
+        shared.stack_count.fetch_add(1, Ordering::Relaxed);
+        stack
+    }
+
+    /// Give the stack of a freed thread back to the process, does nothing if the process exited since
+    /// its address space is already gone
+    pub fn dealloc_stack(&mut self, process: Process, stack: Stack) {
+        let Some(shared) = shared_checked(&process) else {
+            return;
+        };
+
+        let mut frames = DeferredFrames::default();
+        self.mapper(
+            // SAFETY: The stack was allocated by the process, and its thread was freed
+            |_s, mapper, _allocator| unsafe { shared.stacks.lock().dealloc_stack(mapper, &mut frames, stack) },
+            process,
+        );
+
+        // The other threads of the process might still have the stack cached in their TLB
+        shared.active_cores.shootdown();
+        frames.free();
+        shared.stack_count.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Zero the stack of a freed thread, before it's reused by another thread of the process
+    pub fn zero_stack(&mut self, process: Process, stack: &Stack) {
+        let shared = shared(&process);
+        self.mapper(|_s, mapper, _allocator| shared.stacks.lock().zero_stack(mapper, stack), process);
+    }
+
+    /// Map `length` bytes of zeroed memory into the process, either at `address` or at the first free
+    /// range when it's [`None`]. Returns the start of the mapping.
+    pub fn map_memory(
+        &mut self,
+        process: Process,
+        address: Option<VirtAddr>,
+        length: u64,
+        protection: Protection,
+    ) -> Result<VirtAddr, SyscallError> {
+        let shared = shared(&process);
+        let mut regions = shared.regions.lock();
+        let region = match address {
+            Some(address) => regions.reserve(address, length, protection)?,
+            None => regions.reserve_any(length, protection)?,
+        };
+
+        let result = self.mem_access(
+            |_s, mapper, allocator| {
+                // The elf segments aren't tracked as regions, so make sure we don't map over them
+                if region.pages().any(|page| mapper.translate_page(page).is_some()) {
+                    return Err(SyscallError::AddressInUse);
+                }
+
+                for (mapped, page) in region.pages().enumerate() {
+                    let Some(frame) = allocator.allocate_frame() else {
+                        // SAFETY: The pages were mapped just above, and were never accessible by the user
+                        region.pages().take(mapped).for_each(|page| unsafe { mapper.unmap(page, allocator) });
+                        return Err(SyscallError::OutOfMemory);
+                    };
+
+                    // SAFETY: The frame is freshly allocated, and the page is mapped as writable in the
+                    // active page table so zeroing it is fine
+                    unsafe {
+                        mapper.map_to(page, frame, EntryFlags::WRITABLE, allocator);
+                        core::ptr::write_bytes(page.start_address().as_mut_ptr::<u8>(), 0, PAGE_SIZE as usize);
+                    }
+                }
+
+                // SAFETY: Only the pages mapped above are changed
+                region.pages().for_each(|page| unsafe { mapper.change_flags(page, |_| region.entry_flags()) });
+
+                Ok(region.start())
+            },
+            process,
+        );
+
+        if result.is_err() {
+            regions.release(region.start(), length).expect("The region was reserved above");
+        }
+
+        result
+    }
+
+    /// Unmap `length` bytes starting at `address`, the range must be fully mapped by [`Self::map_memory`]
+    /// or [`Self::map_shared_memory`], and can't cover a shared memory mapping partially
+    pub fn unmap_memory(&mut self, process: Process, address: VirtAddr, length: u64) -> Result<(), SyscallError> {
+        let shared = shared(&process);
+        let mut regions = shared.regions.lock();
+        let mut shared_mappings = shared.shared_mappings.lock();
+        shared_mappings.check_unmap(address, length)?;
+        let released = regions.release(address, length)?;
+        let unmapped_shared = shared_mappings.remove(address, length);
+
+        // The regions stay locked until the pages are unmapped, see pin_mappings
+        let frames = self.mapper(
+            |_s, mapper, _allocator| {
+                let mut frames = DeferredFrames::default();
+                for page in released.iter().flat_map(|region| region.pages()) {
+                    // SAFETY: The regions are only mapped by map_memory or map_shared_memory, and the user
+                    // asked for them to be removed. The shared frames are freed with the last reference
+                    unsafe {
+                        if shared_memory::contains(&unmapped_shared, page.start_address()) {
+                            mapper.unmap_page(page);
+                        } else {
+                            mapper.unmap(page, &mut frames);
+                        }
+                    }
+                }
+                frames
+            },
+            process,
+        );
+        drop((regions, shared_mappings));
+
+        // The other cores running the process might still access the frames through their TLB
+        shared.active_cores.shootdown();
+        frames.free();
+        drop(unmapped_shared);
+        Ok(())
+    }
+
+    /// Map the whole shared `memory` object into the process, either at `address` or at the first free
+    /// range when it's [`None`]. Returns the start of the mapping
+    pub fn map_shared_memory(
+        &mut self,
+        process: Process,
+        memory: Arc<SharedMemory>,
+        address: Option<VirtAddr>,
+        protection: Protection,
+    ) -> Result<VirtAddr, SyscallError> {
+        let shared = shared(&process);
+        let mut regions = shared.regions.lock();
+        let region = match address {
+            Some(address) => regions.reserve(address, memory.length(), protection)?,
+            None => regions.reserve_any(memory.length(), protection)?,
+        };
+
+        let result = self.mapper(
+            |_s, mapper, allocator| {
+                // The elf segments aren't tracked as regions, so make sure we don't map over them
+                if region.pages().any(|page| mapper.translate_page(page).is_some()) {
+                    return Err(SyscallError::AddressInUse);
+                }
+
+                for (page, frame) in region.pages().zip(memory.frames()) {
+                    // SAFETY: The range was free, and the frames stay alive as long as the mapping holds
+                    // a reference to the object
+                    unsafe { mapper.map_to(page, *frame, region.entry_flags(), allocator) };
+                }
+
+                Ok(region.start())
+            },
+            process,
+        );
+
+        match result {
+            Ok(start) => shared.shared_mappings.lock().insert(start, memory),
+            Err(_) => {
+                regions.release(region.start(), memory.length()).expect("The region was reserved above");
+            }
+        }
+
+        result
+    }
+
+    /// Unmap `length` bytes starting at `address` without freeing the frames, the range must be fully
+    /// mapped by [`Self::map_memory`]. Returns the frames, to be mapped in another process with
+    /// [`Self::place_grant`]
+    pub fn take_grant(&mut self, process: Process, address: VirtAddr, length: u64) -> Result<Grant, SyscallError> {
+        let shared = shared(&process);
+        let mut regions = shared.regions.lock();
+        // The frames of a shared memory object belong to the object
+        if shared.shared_mappings.lock().overlaps(address, length) {
+            return Err(SyscallError::InvalidArgument);
+        }
+        let released = regions.release(address, length)?;
+
+        // The regions stay locked until the pages are unmapped, see pin_mappings
+        let frames = self.mapper(
+            |_s, mapper, _allocator| {
+                released
+                    .iter()
+                    .flat_map(|region| region.pages())
+                    // SAFETY: The regions are only mapped by map_memory, and the user asked for them to be moved
+                    .map(|page| match unsafe { mapper.unmap_page(page) } {
+                        AnyFrame::Frame4K(frame) => frame,
+                        _ => unreachable!("Regions are mapped with 4K pages"),
+                    })
+                    .collect()
+            },
+            process,
+        );
+        drop(regions);
+
+        // The frames must not be reachable by the other cores running the process once they're granted
+        shared.active_cores.shootdown();
+        Ok(Grant::new(frames))
+    }
+
+    /// Map the frames of `grant` at the first free range of the process as readable and writable,
+    /// returns the start of the mapping
+    pub fn place_grant(&mut self, process: Process, grant: Grant) -> Result<VirtAddr, SyscallError> {
+        let shared = shared(&process);
+        let length = grant.len() as u64 * PAGE_SIZE;
+        let region = shared.regions.lock().reserve_any(length, Protection::READ | Protection::WRITE)?;
+
+        self.mapper(
+            |_s, mapper, allocator| {
+                for (page, frame) in region.pages().zip(grant.into_frames()) {
+                    // SAFETY: The range was free in the tracker, and the frames were unmapped from the sender
+                    unsafe { mapper.map_to(page, frame, region.entry_flags(), allocator) };
+                }
+            },
+            process,
+        );
+
+        Ok(region.start())
+    }
+
+    /// Change the protection of `length` bytes starting at `address`, the range must be fully mapped
+    /// by [`Self::map_memory`]
+    pub fn protect_memory(
+        &mut self,
+        process: Process,
+        address: VirtAddr,
+        length: u64,
+        protection: Protection,
+    ) -> Result<(), SyscallError> {
+        let shared = shared(&process);
+        let mut regions = shared.regions.lock();
+        let region = regions.protect(address, length, protection)?;
+
+        // The regions stay locked until the pages are changed, see pin_mappings
+        self.mapper(
+            // SAFETY: The regions are only mapped by map_memory, so no kernel data lives there
+            |_s, mapper, _allocator| {
+                region.pages().for_each(|page| unsafe { mapper.change_flags(page, |_| region.entry_flags()) })
+            },
+            process,
+        );
+        drop(regions);
+
+        // The other cores running the process might have cached the old protection
+        shared.active_cores.shootdown();
+        Ok(())
+    }
+
+    /// Copy `destination.len()` bytes from the process memory at `source`, see [`user_memory::copy_from_user`]
+    pub fn copy_from_user(
+        &mut self,
+        process: Process,
+        destination: &mut [u8],
+        source: VirtAddr,
+    ) -> Result<(), SyscallError> {
+        self.mem_access(|_s, mapper, _allocator| user_memory::copy_from_user(mapper, destination, source), process)
+    }
+
+    /// Check that `length` bytes at `address` are accessible by the process, see
+    /// [`user_memory::validate_user_range`]
+    pub fn validate_user_range(
+        &mut self,
+        process: Process,
+        address: VirtAddr,
+        length: usize,
+        write: bool,
+    ) -> Result<(), SyscallError> {
+        self.mapper(
+            |_s, mapper, _allocator| user_memory::validate_user_range(mapper, address, length, write).map(|_| ()),
+            process,
+        )
+    }
+
+    /// Copy `source` into the process memory at `destination`, see [`user_memory::copy_to_user`]
+    pub fn copy_to_user(&mut self, process: Process, destination: VirtAddr, source: &[u8]) -> Result<(), SyscallError> {
+        self.mem_access(|_s, mapper, _allocator| user_memory::copy_to_user(mapper, destination, source), process)
     }
 
     pub fn alloc_thread(&mut self, parent: Process, thread: Thread) {
-        shared(&parent).threads.lock().insert(thread.id());
+        shared(&parent).threads.lock().insert(thread.id(), 0);
+    }
+
+    /// Remove the thread from its process, returns the tasks joining it
+    pub fn free_thread(&mut self, thread: Thread) -> Vec<TaskBlock> {
+        let Some(process) = find_by_thread(&thread) else {
+            return Vec::new();
+        };
+
+        let shared = shared(&process);
+        let mut threads = shared.threads.lock();
+        threads.remove(&thread.id());
+        shared.signals.lock().remove_thread(thread.id());
+        shared.joiners.lock().remove(&thread.id()).unwrap_or_default()
+    }
+
+    /// Charge `nanos` of cpu time to the thread and to its process
+    pub fn charge_cpu_time(&mut self, process: Process, thread: Thread, nanos: u64) {
+        let shared = shared(&process);
+        shared.cpu_time.fetch_add(nanos, Ordering::Relaxed);
+        if let Some(cpu_time) = shared.threads.lock().get_mut(&thread.id()) {
+            *cpu_time += nanos;
+        }
+    }
+
+    /// The live process `pid`
+    pub fn find(&self, pid: usize) -> Result<Process, SyscallError> {
+        find_by_pid(pid).map(|(process, _)| process)
+    }
+
+    /// Set what the process does when it receives `signal`, returns the previous disposition
+    pub fn set_signal_disposition(
+        &mut self,
+        process: Process,
+        signal: Signal,
+        disposition: Disposition,
+    ) -> Result<Disposition, SyscallError> {
+        shared(&process).signals.lock().set_disposition(signal, disposition)
     }
 
-    pub fn free_thread(&mut self, thread: Thread) {
-        if let Some(process) = find_by_thread(&thread) {
-            shared(&process).threads.lock().remove(&thread.id());
+    /// Send `signal` to the process, or to its thread `thread`. Returns true if the process must be
+    /// terminated right away
+    pub fn send_signal(
+        &mut self,
+        process: Process,
+        thread: Option<NonZeroUsize>,
+        signal: Signal,
+    ) -> Result<bool, SyscallError> {
+        let shared = shared_checked(&process).ok_or(SyscallError::NotFound)?;
+        let threads = shared.threads.lock();
+        if thread.is_some_and(|thread| !threads.contains_key(&thread)) {
+            return Err(SyscallError::NotFound);
         }
+
+        Ok(shared.signals.lock().send(signal, thread))
     }
 
-    pub fn free(&mut self, process: Process) {
+    /// Take the next signal the task must handle, See [`SignalState::take`]
+    pub fn take_signal(&mut self, task: TaskBlock) -> Option<Delivery> {
+        shared(&task.process).signals.lock().take(task.thread.id())
+    }
+
+    /// The resources used by the live process with the lowest pid greater or equal to `pid`
+    pub fn info(&self, pid: usize) -> Result<ProcessInfo, SyscallError> {
+        let (process, shared) = GLOBAL_PROCESS_DATA.read().find_from(pid).ok_or(SyscallError::NotFound)?;
+        let stacks = shared.stack_count.load(Ordering::Relaxed);
+
+        Ok(ProcessInfo {
+            pid: process.pid(),
+            cpu_time: shared.cpu_time.load(Ordering::Relaxed),
+            threads: shared.threads.lock().len(),
+            mapped_pages: shared.regions.lock().mapped_pages() + stacks * USER_STACK_PAGES,
+            stacks,
+        })
+    }
+
+    /// The resources used by the thread of the live process `pid` with the lowest id greater or equal
+    /// to `thread_id`
+    pub fn thread_info(&self, pid: usize, thread_id: usize) -> Result<ThreadInfo, SyscallError> {
+        let (_, shared) = find_by_pid(pid)?;
+        let threads = shared.threads.lock();
+        let (id, cpu_time) = threads
+            .iter()
+            .filter(|(id, _)| id.get() >= thread_id)
+            .min_by_key(|(id, _)| **id)
+            .ok_or(SyscallError::NotFound)?;
+
+        Ok(ThreadInfo { thread_id: id.get(), cpu_time: *cpu_time })
+    }
+
+    /// Register `task` as joining the thread `id` of the same process, returns false if the thread
+    /// already exited, in which case the task must not be blocked
+    pub fn join(&mut self, task: TaskBlock, id: NonZeroUsize) -> bool {
+        let shared = shared(&task.process);
+        let threads = shared.threads.lock();
+        if !threads.contains_key(&id) {
+            return false;
+        }
+
+        shared.joiners.lock().entry(id).or_default().push(task);
+        true
+    }
+
+    /// Queue `task` on the futex at `address` if the u32 there still holds `expected`, returns false
+    /// if it doesn't, in which case the task must not be blocked
+    pub fn futex_wait(&mut self, task: TaskBlock, address: VirtAddr, expected: u32) -> Result<bool, SyscallError> {
+        let shared = shared(&task.process);
+        // Held while reading the value, so a wake can't slip between the check and the queueing
+        let mut futexes = shared.futexes.lock();
+
+        let mut value = [0; size_of::<u32>()];
+        self.copy_from_user(task.process, &mut value, address)?;
+        if u32::from_ne_bytes(value) != expected {
+            return Ok(false);
+        }
+
+        futexes.entry(address).or_default().push_back(task);
+        Ok(true)
+    }
+
+    /// Dequeue up to `count` tasks blocked on the futex at `address`, in the order they blocked
+    pub fn futex_wake(&mut self, process: Process, address: VirtAddr, count: usize) -> Vec<TaskBlock> {
+        let shared = shared(&process);
+        let mut futexes = shared.futexes.lock();
+        let Some(waiters) = futexes.get_mut(&address) else {
+            return Vec::new();
+        };
+
+        let mut woken = Vec::new();
+        while woken.len() < count
+            && let Some(task) = waiters.pop_front()
+        {
+            // The thread might have been killed while blocked
+            if task.valid() {
+                woken.push(task);
+            }
+        }
+
+        if waiters.is_empty() {
+            futexes.remove(&address);
+        }
+
+        woken
+    }
+
+    /// Dequeue `task` from the futex at `address` once its wait timed out, returns false if it was
+    /// already dequeued by a wake
+    pub fn futex_cancel(&mut self, task: TaskBlock, address: VirtAddr) -> bool {
+        let shared = shared(&task.process);
+        let mut futexes = shared.futexes.lock();
+        let Some(waiters) = futexes.get_mut(&address) else {
+            return false;
+        };
+
+        let Some(position) = waiters.iter().position(|waiter| *waiter == task) else {
+            return false;
+        };
+
+        waiters.remove(position);
+        if waiters.is_empty() {
+            futexes.remove(&address);
+        }
+        true
+    }
+
+    pub fn file(&mut self, process: Process, fd: usize) -> Result<File, SyscallError> {
+        shared(&process).files.lock().get(fd)
+    }
+
+    /// Open `file` in the process, returns its file descriptor
+    pub fn open_file(&mut self, process: Process, file: File) -> Result<usize, SyscallError> {
+        shared(&process).files.lock().insert(file)
+    }
+
+    pub fn close_file(&mut self, process: Process, fd: usize) -> Result<(), SyscallError> {
+        let shared = shared(&process);
+        let closed = shared.files.lock().close(fd)?;
+        // Dropped once the table lock is released, see [`FileTable::close`]
+        drop(closed);
+        Ok(())
+    }
+
+    pub fn dup_file(&mut self, process: Process, fd: usize) -> Result<usize, SyscallError> {
+        shared(&process).files.lock().dup(fd)
+    }
+
+    pub fn capability(&mut self, process: Process, handle: usize) -> Result<Capability, SyscallError> {
+        shared(&process).capabilities.lock().get(handle)
+    }
+
+    /// Give `capability` to the process, returns its handle
+    pub fn insert_capability(&mut self, process: Process, capability: Capability) -> Result<usize, SyscallError> {
+        shared(&process).capabilities.lock().insert(capability)
+    }
+
+    /// Drop the capability `handle`, the port is closed if it's the receive capability
+    pub fn remove_capability(&mut self, process: Process, handle: usize) -> Result<(), SyscallError> {
+        let shared = shared(&process);
+        let removed = shared.capabilities.lock().remove(handle)?;
+        if let Ok(port) = removed.receive_port() {
+            // Dropped once the table lock is released, the queued grants are freed
+            drop(port.close());
+        }
+        Ok(())
+    }
+
+    /// Terminate the process with the exit `code`, the exit code is kept (as a zombie) until the
+    /// parent collects it with [`Self::wait`]. Returns the waiter that collected the exit code, and
+    /// must be woken up with the pid of the process.
+    pub fn exit(&mut self, process: Process, code: i32) -> Option<TaskBlock> {
+        // Another thread of the process might be exiting on a different core
+        if !invalidate(&process) {
+            return None;
+        }
+
         let shared = shared(&process);
         // TODO: For now we'll just wait for the threads to yield and free them, in the begin event,
         // since that thread doesn't belong to any process it'll get killed in the begin event
         shared.threads.lock().clear();
-        free(process);
+
+        // Dropped once the table lock is released, closing the pipes wakes up the other ends
+        let files = shared.files.lock().close_all();
+        drop(files);
+
+        let capabilities = shared.capabilities.lock().remove_all();
+        for port in capabilities.iter().flatten().filter_map(|capability| capability.receive_port().ok()) {
+            drop(port.close());
+        }
+        drop(capabilities);
+
+        // The other processes might still map the shared memory objects, so only their mappings go away,
+        // every other page of the lower half (the elf segments, the stacks and the regions) is owned by
+        // the process. The p3 tables are shared by the page tables of every core, so clearing them once
+        // empties the address space on every core
+        let shared_mappings = shared.shared_mappings.lock().remove_all();
+        let mut frames = DeferredFrames::default();
+        let tables = self.mapper(
+            |_s, mapper, _allocator| {
+                for (start, memory) in &shared_mappings {
+                    for page in Page::<Size4K>::range(Page::containing_address(*start), memory.frames().len() as u64) {
+                        // SAFETY: The process is dead, and the frames are freed with the last reference
+                        unsafe { mapper.unmap_page(page) };
+                    }
+                }
+
+                // SAFETY: The process is dead, and the shared memory mappings were removed above
+                unsafe { mapper.clear(RootRecurseLowerHalf::start()..RootRecurseLowerHalf::end(), &mut frames) };
+                unlink_lower_half(mapper)
+            },
+            process,
+        );
+
+        // The other cores running the process flush their TLB, and switch away from it since its threads
+        // were freed, only then the frames can be reused
+        shared.active_cores.shootdown();
+        frames.free();
+        drop(shared_mappings);
+
+        // The p3 tables are still linked in the page tables of the other cores, the slot is only released
+        // after the packet is sent so a reused slot links its new tables after they're unlinked
+        let tables = Arc::new(ReleasedFrames::new(tables));
+        PageTablePacket { id: process.id, change: PageTableChange::Unlink(tables) }.broadcast(false);
+
+        let orphans = shared.family.lock().exit();
+        orphans.iter().for_each(|Zombie { pid, .. }| release(*pid));
+
+        let Some(parent) = shared.parent.and_then(|parent| shared_checked(&parent)) else {
+            release(process.id);
+            return None;
+        };
+
+        // Child is ignored by default, so it never terminates the parent
+        parent.signals.lock().send(Signal::Child, None);
+
+        let child_exit = parent.family.lock().child_exited(process.id, code);
+        match child_exit {
+            ChildExit::Collected(Waiter { task, status, .. }) => {
+                release(process.id);
+
+                // The status address was only checked to be in the lower half, so the waiter gets the pid
+                // even if it can't get the exit code
+                if let Some(status) = status {
+                    let _ = self.copy_to_user(task.process, status, &code.to_ne_bytes());
+                }
+
+                Some(task)
+            }
+            ChildExit::Zombie => None,
+            ChildExit::Orphan => {
+                release(process.id);
+                None
+            }
+        }
     }
 
-    pub fn alloc(&mut self) -> Process {
-        let process = alloc_shared();
+    /// Collect the exit code of a child matching `pid` (any child if [`None`]), writing it to `status`.
+    /// Returns the pid of the collected child, or [`None`] if the calling thread must block until the
+    /// child exits (the pid is then returned when it's woken up).
+    pub fn wait(
+        &mut self,
+        task: TaskBlock,
+        pid: Option<usize>,
+        status: Option<VirtAddr>,
+    ) -> Result<Option<usize>, SyscallError> {
+        let shared = shared(&task.process);
+        let mut family = shared.family.lock();
+
+        let Some(Zombie { pid, code }) = family.collect(pid)? else {
+            family.wait(Waiter { task, pid, status });
+            return Ok(None);
+        };
+
+        drop(family);
+        release(pid);
+
+        if let Some(status) = status {
+            self.copy_to_user(task.process, status, &code.to_ne_bytes())?;
+        }
+
+        Ok(Some(pid))
+    }
+
+    pub fn alloc(&mut self, parent: Option<Process>) -> Process {
+        let process = alloc_shared(parent);
+        if let Some(parent) = parent {
+            let parent = shared(&parent);
+            parent.family.lock().adopt(process.id);
+            *shared(&process).capabilities.lock() = parent.capabilities.lock().inherit();
+            *shared(&process).files.lock() = parent.files.lock().inherit();
+        }
+
+        // The unlink packet of a reused slot was sent before its previous process released it, the old p3
+        // tables are unlinked once it's handled
+        self.check_ipp();
         if self.page_tables.get(process.id).is_some() {
-            // TODO: Clean up the page tables
-            self.mapper(|_s, _mapper, _allocator| {}, process);
+            let tables = self.table_mapper(process.id, |_s, mapper, allocator| {
+                mapper.populate_p4_lower_half(allocator);
+                lower_half_tables(mapper)
+            });
+            PageTablePacket { id: process.id, change: PageTableChange::Link(Arc::new(tables)) }.broadcast(false);
         } else {
             // SAFETY: The mutable exclusivity of the page table is ensure by page_table_modification_lock
             let orignal_table = Arc::new(unsafe {
@@ -175,7 +827,8 @@ impl ProcessPipeline {
                 )
             });
 
-            ExpandSharedPacket { table_template: Arc::clone(&orignal_table) }.broadcast(false);
+            PageTablePacket { id: process.id, change: PageTableChange::Expand(Arc::clone(&orignal_table)) }
+                .broadcast(false);
 
             // SAFETY: The mutable exclusivity of the page table is ensure by page_table_modification_lock
             self.page_tables.push(Some(unsafe { copy_mappings(InactivePageCopyOption::lower_half(), &orignal_table) }));
@@ -197,10 +850,29 @@ impl Process {
     pub fn valid(&self) -> bool {
         self.signature == sigature(self)
     }
+
+    /// The id of the process as seen by the userland, stays reserved until the exit code is collected
+    pub fn pid(&self) -> usize {
+        self.id
+    }
+}
+
+/// Run `f` with the mappings of the process pinned, they can't be unmapped or have their protection
+/// changed until it returns. `f` must not map or unmap memory in the process
+pub fn pin_mappings<R>(process: Process, f: impl FnOnce() -> R) -> R {
+    let shared = shared(&process);
+    let _regions = shared.regions.lock();
+    f()
 }
 
-fn free(process: Process) {
-    GLOBAL_PROCESS_DATA.write().free(process);
+/// Make every reference to the process invalid, returns false if it was already invalid
+fn invalidate(process: &Process) -> bool {
+    GLOBAL_PROCESS_DATA.read().invalidate(process)
+}
+
+/// Make the id of an invalidated process available again
+fn release(id: usize) {
+    GLOBAL_PROCESS_DATA.write().release(id);
 }
 
 fn sigature(process: &Process) -> usize {
@@ -211,14 +883,26 @@ fn find_by_thread(thread: &Thread) -> Option<Process> {
     GLOBAL_PROCESS_DATA.read().find_by_id(thread)
 }
 
-fn alloc_shared() -> Process {
-    GLOBAL_PROCESS_DATA.write().alloc()
+fn find_by_pid(pid: usize) -> Result<(Process, Arc<ProcessShared>), SyscallError> {
+    GLOBAL_PROCESS_DATA.read().find_from(pid).filter(|(process, _)| process.id == pid).ok_or(SyscallError::NotFound)
+}
+
+fn alloc_shared(parent: Option<Process>) -> Process {
+    GLOBAL_PROCESS_DATA.write().alloc(parent)
 }
 
 fn shared(process: &Process) -> Arc<ProcessShared> {
     GLOBAL_PROCESS_DATA.read().shared(process)
 }
 
+/// Same as [`shared`] but returns [`None`] if the process is invalid
+fn shared_checked(process: &Process) -> Option<Arc<ProcessShared>> {
+    let pool = GLOBAL_PROCESS_DATA.read();
+    let shared = pool.shared(process);
+    let valid = *shared.signature.lock() == process.signature;
+    valid.then_some(shared)
+}
+
 static GLOBAL_PROCESS_DATA: RwLock<GlobalProcessDataPool> = RwLock::new(GlobalProcessDataPool::new());
 
 #[derive(Default)]
@@ -237,26 +921,44 @@ impl GlobalProcessDataPool {
     }
 
     fn find_by_id(&self, thread: &Thread) -> Option<Process> {
-        // TODO: probably use a map on the pool instead, but HashSet on the process threads is prob
+        // TODO: probably use a map on the pool instead, but HashMap on the process threads is prob
         // enough
         for (id, shared) in self.pool.iter().enumerate() {
-            if shared.threads.lock().contains(&thread.id()) {
+            if shared.threads.lock().contains_key(&thread.id()) {
                 return Some(Process { id, signature: *shared.signature.lock() });
             }
         }
         None
     }
 
-    fn free(&mut self, process: Process) {
-        self.free_id.push(process.id);
+    /// The valid process with the lowest id greater or equal to `id`
+    fn find_from(&self, id: usize) -> Option<(Process, Arc<ProcessShared>)> {
+        self.pool.iter().enumerate().skip(id).find_map(|(id, shared)| {
+            let signature = *shared.signature.lock();
+            (signature != 0).then(|| (Process { id, signature }, Arc::clone(shared)))
+        })
+    }
+
+    fn invalidate(&self, process: &Process) -> bool {
+        let mut signature = self.pool[process.id].signature.lock();
+        if *signature != process.signature {
+            return false;
+        }
+
         // Signature 0 is always invalid
-        *self.pool[process.id].signature.lock() = 0;
+        *signature = 0;
+        true
     }
 
-    fn alloc(&mut self) -> Process {
+    fn release(&mut self, id: usize) {
+        debug_assert_eq!(*self.pool[id].signature.lock(), 0, "Released a process that is still valid");
+        self.free_id.push(id);
+    }
+
+    fn alloc(&mut self, parent: Option<Process>) -> Process {
         if let Some(id) = self.free_id.pop() {
             let free = &mut self.pool[id];
-            let new = ProcessShared::new();
+            let new = ProcessShared::new(parent);
             let signature = *new.signature.lock();
             *free = Arc::new(new);
 
@@ -264,7 +966,7 @@ impl GlobalProcessDataPool {
         }
 
         let id = self.pool.len();
-        let new = ProcessShared::new();
+        let new = ProcessShared::new(parent);
         let signature = *new.signature.lock();
         self.pool.push(Arc::new(new));
 
@@ -274,14 +976,36 @@ impl GlobalProcessDataPool {
 
 struct ProcessShared {
     stacks: Mutex<StackAllocator>,
-    threads: Mutex<HashSet<NonZeroUsize>>,
+    /// The number of stacks allocated by `stacks`
+    stack_count: AtomicUsize,
+    /// The threads of the process, along with the cpu time they used in nanoseconds
+    threads: Mutex<HashMap<NonZeroUsize, u64>>,
+    /// The cpu time used by every thread of the process in nanoseconds, including the exited ones
+    cpu_time: AtomicU64,
+    /// The tasks blocked until a thread is freed, always locked after `threads`
+    joiners: Mutex<HashMap<NonZeroUsize, Vec<TaskBlock>>>,
+    regions: Mutex<RegionTracker>,
+    files: Mutex<FileTable>,
+    capabilities: Mutex<CapabilityTable>,
+    /// Always locked after `regions`
+    shared_mappings: Mutex<SharedMappings>,
+    /// Always locked after `threads`
+    signals: Mutex<SignalState>,
+    /// The tasks blocked on a futex, keyed on its user address
+    futexes: Mutex<HashMap<VirtAddr, VecDeque<TaskBlock>>>,
     signature: Mutex<usize>,
 
+    /// [`None`] for the processes spawned by the kernel (e.g. init)
+    parent: Option<Process>,
+    family: Mutex<Family>,
+
     page_table_modification_lock: Mutex<()>,
+    /// The cores with the page table of the process loaded
+    active_cores: ActiveCores,
 }
 
 impl ProcessShared {
-    pub fn new() -> Self {
+    pub fn new(parent: Option<Process>) -> Self {
         // SAFETY: The safety section of the create_mappings doesn't apply when the used variant of
         // [`InactivePageCopyOption`] is Empty
         Self {
@@ -291,19 +1015,72 @@ impl ProcessShared {
                     (userland::STACK_START + userland::STACK_MAX_SIZE).into(),
                 ),
                 true,
+                true,
             )
             .into(),
-            threads: HashSet::new().into(),
+            stack_count: AtomicUsize::new(0),
+            threads: HashMap::new().into(),
+            cpu_time: AtomicU64::new(0),
+            joiners: HashMap::new().into(),
+            regions: RegionTracker::new().into(),
+            files: FileTable::new().into(),
+            capabilities: CapabilityTable::new().into(),
+            shared_mappings: SharedMappings::new().into(),
+            signals: SignalState::new().into(),
+            futexes: HashMap::new().into(),
             signature: sig().into(),
 
+            parent,
+            family: Family::new().into(),
+
             page_table_modification_lock: ().into(),
+            active_cores: ActiveCores::default(),
         }
     }
 }
 
+/// The p3 tables of the lower half of a page table
+fn lower_half_tables(mapper: &mut Mapper<RootRecurseLowerHalf>) -> Vec<Frame<Size4K>> {
+    let p4 = mapper.p4();
+    (RootRecurseLowerHalf::start()..RootRecurseLowerHalf::end())
+        .filter_map(|index| p4[index as usize].pointed_table())
+        .collect()
+}
+
+/// Link the p3 tables of a process, they're shared by the page tables of every core
+fn link_lower_half(mapper: &mut Mapper<RootRecurseLowerHalf>, tables: &[Frame<Size4K>]) {
+    let p4 = mapper.p4_mut();
+    for (index, table) in (RootRecurseLowerHalf::start()..RootRecurseLowerHalf::end()).zip(tables) {
+        p4[index as usize].set(*table, EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE);
+    }
+}
+
+/// Unlink the p3 tables of a process without freeing them, returns the unlinked tables
+fn unlink_lower_half(mapper: &mut Mapper<RootRecurseLowerHalf>) -> Vec<Frame<Size4K>> {
+    let tables = lower_half_tables(mapper);
+    let p4 = mapper.p4_mut();
+    (RootRecurseLowerHalf::start()..RootRecurseLowerHalf::end()).for_each(|index| p4[index as usize].set_unused());
+    tlb::full_flush();
+
+    tables
+}
+
+/// Keeps the page table of every core in sync for the process with the id, the packets of a slot are
+/// handled in the order they're sent
 #[derive(Clone, IPPacket)]
-struct ExpandSharedPacket {
-    table_template: Arc<InactivePageTable<RootRecurseLowerHalf>>,
+struct PageTablePacket {
+    id: usize,
+    change: PageTableChange,
+}
+
+#[derive(Clone)]
+enum PageTableChange {
+    /// A new slot, the page table is copied from the template
+    Expand(Arc<InactivePageTable<RootRecurseLowerHalf>>),
+    /// A reused slot, its new p3 tables are linked
+    Link(Arc<Vec<Frame<Size4K>>>),
+    /// The process exited, its p3 tables are freed once every core unlinked them
+    Unlink(Arc<ReleasedFrames>),
 }
 
 static SIG: AtomicUsize = AtomicUsize::new(1);
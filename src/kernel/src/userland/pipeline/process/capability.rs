@@ -0,0 +1,103 @@
+//! The capability table of a process, a capability is the only way for a process to reach a kernel
+//! object (a port or a shared memory object).
+//!
+//! The receive capability of a port stays with the process that created it, every other port
+//! capability only allows sending. A spawned process starts with a copy of every capability of its
+//! parent (See [`Capability::transferred`]), at the same handles, so the parent can pass them as
+//! program arguments.
+
+use alloc::{sync::Arc, vec::Vec};
+use hotline::SyscallError;
+
+use super::{port::Port, shared_memory::SharedMemory};
+
+/// The maximum number of capabilities held at once by a process
+pub const MAX_CAPABILITIES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum Capability {
+    Port {
+        port: Arc<Port>,
+        /// Only the capability returned when creating the port can receive
+        receive: bool,
+    },
+    SharedMemory(Arc<SharedMemory>),
+}
+
+impl Capability {
+    /// The copy of the capability given to another process, a port can only be sent to through it
+    pub fn transferred(&self) -> Self {
+        match self {
+            Self::Port { port, .. } => Self::Port { port: Arc::clone(port), receive: false },
+            Self::SharedMemory(memory) => Self::SharedMemory(Arc::clone(memory)),
+        }
+    }
+
+    /// The port behind a send or a receive capability
+    pub fn port(&self) -> Result<&Arc<Port>, SyscallError> {
+        match self {
+            Self::Port { port, .. } => Ok(port),
+            _ => Err(SyscallError::InvalidCapability),
+        }
+    }
+
+    /// The port behind a receive capability
+    pub fn receive_port(&self) -> Result<&Arc<Port>, SyscallError> {
+        match self {
+            Self::Port { port, receive: true } => Ok(port),
+            _ => Err(SyscallError::InvalidCapability),
+        }
+    }
+
+    pub fn shared_memory(&self) -> Result<&Arc<SharedMemory>, SyscallError> {
+        match self {
+            Self::SharedMemory(memory) => Ok(memory),
+            _ => Err(SyscallError::InvalidCapability),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CapabilityTable {
+    capabilities: Vec<Option<Capability>>,
+}
+
+impl CapabilityTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A table holding a transferred copy of every capability of `self`, at the same handles
+    pub fn inherit(&self) -> Self {
+        let capabilities = self.capabilities.iter().map(|capability| capability.as_ref().map(Capability::transferred));
+        Self { capabilities: capabilities.collect() }
+    }
+
+    pub fn get(&self, handle: usize) -> Result<Capability, SyscallError> {
+        self.capabilities.get(handle).cloned().flatten().ok_or(SyscallError::InvalidCapability)
+    }
+
+    /// Store `capability` at the lowest free handle, returns the handle
+    pub fn insert(&mut self, capability: Capability) -> Result<usize, SyscallError> {
+        if let Some(handle) = self.capabilities.iter().position(Option::is_none) {
+            self.capabilities[handle] = Some(capability);
+            return Ok(handle);
+        }
+
+        if self.capabilities.len() >= MAX_CAPABILITIES {
+            return Err(SyscallError::TooManyCapabilities);
+        }
+
+        self.capabilities.push(Some(capability));
+        Ok(self.capabilities.len() - 1)
+    }
+
+    pub fn remove(&mut self, handle: usize) -> Result<Capability, SyscallError> {
+        self.capabilities.get_mut(handle).and_then(Option::take).ok_or(SyscallError::InvalidCapability)
+    }
+
+    /// Remove every capability, the ports of the receive capabilities must be closed by the caller
+    pub fn remove_all(&mut self) -> Vec<Option<Capability>> {
+        core::mem::take(&mut self.capabilities)
+    }
+}
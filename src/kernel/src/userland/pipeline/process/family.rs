@@ -0,0 +1,117 @@
+//! Tracks the children of a process, the exit codes that haven't been collected yet (zombies), and
+//! the threads blocked waiting for them.
+//!
+//! The family only deals with the bookkeeping, releasing the process ids and waking up the waiters is
+//! done by the [`ProcessPipeline`](super::ProcessPipeline).
+
+use alloc::vec::Vec;
+use hashbrown::HashSet;
+use hotline::SyscallError;
+use pager::address::VirtAddr;
+
+use crate::userland::pipeline::TaskBlock;
+
+/// A child that exited, its process id is kept reserved until the exit code is collected
+#[derive(Debug, Clone, Copy)]
+pub struct Zombie {
+    pub pid: usize,
+    pub code: i32,
+}
+
+/// A thread blocked until a child exits
+#[derive(Debug, Clone, Copy)]
+pub struct Waiter {
+    pub task: TaskBlock,
+    /// [`None`] when waiting for any child
+    pub pid: Option<usize>,
+    /// Where the exit code is written to
+    pub status: Option<VirtAddr>,
+}
+
+impl Waiter {
+    fn accepts(&self, pid: usize) -> bool {
+        self.pid.is_none_or(|waiting| waiting == pid)
+    }
+}
+
+/// What happened to the exit code of a child
+#[derive(Debug)]
+pub enum ChildExit {
+    /// A waiter collected the exit code, it must be woken up
+    Collected(Waiter),
+    /// The exit code is kept until a waiter collects it
+    Zombie,
+    /// The parent exited, nobody will ever collect the exit code
+    Orphan,
+}
+
+#[derive(Debug, Default)]
+pub struct Family {
+    /// Both the alive and the zombie children
+    children: HashSet<usize>,
+    zombies: Vec<Zombie>,
+    waiters: Vec<Waiter>,
+    exited: bool,
+}
+
+impl Family {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn adopt(&mut self, pid: usize) {
+        self.children.insert(pid);
+    }
+
+    /// Take the exit code of a child matching `pid` (any child if [`None`]), returns [`None`] if the
+    /// matching children are still alive
+    pub fn collect(&mut self, pid: Option<usize>) -> Result<Option<Zombie>, SyscallError> {
+        let has_child = match pid {
+            Some(pid) => self.children.contains(&pid),
+            None => !self.children.is_empty(),
+        };
+
+        if !has_child {
+            return Err(SyscallError::NoChild);
+        }
+
+        let Some(index) = self.zombies.iter().position(|zombie| pid.is_none_or(|pid| pid == zombie.pid)) else {
+            return Ok(None);
+        };
+
+        let zombie = self.zombies.swap_remove(index);
+        self.children.remove(&zombie.pid);
+        Ok(Some(zombie))
+    }
+
+    /// Block a thread until a child matching the waiter exits, [`Self::collect`] must have returned
+    /// [`None`] under the same lock
+    pub fn wait(&mut self, waiter: Waiter) {
+        self.waiters.push(waiter);
+    }
+
+    pub fn child_exited(&mut self, pid: usize, code: i32) -> ChildExit {
+        if self.exited {
+            return ChildExit::Orphan;
+        }
+
+        // A waiter belonging to a dead thread would swallow the exit code
+        self.waiters.retain(|waiter| waiter.task.valid());
+
+        if let Some(index) = self.waiters.iter().position(|waiter| waiter.accepts(pid)) {
+            self.children.remove(&pid);
+            return ChildExit::Collected(self.waiters.swap_remove(index));
+        }
+
+        self.zombies.push(Zombie { pid, code });
+        ChildExit::Zombie
+    }
+
+    /// Mark the owning process as exited, returns the zombies that will never be collected
+    pub fn exit(&mut self) -> Vec<Zombie> {
+        self.exited = true;
+        self.children.clear();
+        self.waiters.clear();
+        core::mem::take(&mut self.zombies)
+    }
+}
@@ -0,0 +1,75 @@
+//! The file descriptor table of a process, the processes spawned by the kernel start with
+//! [`STDIN`](hotline::STDIN), [`STDOUT`](hotline::STDOUT) and [`STDERR`](hotline::STDERR) opened to
+//! the console, the other ones inherit the table of their parent.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use hotline::SyscallError;
+
+use super::pipe::PipeEnd;
+
+/// The maximum number of file descriptors opened at once by a process
+pub const MAX_FILES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum File {
+    /// The serial port (and the screen when available), can only be written to
+    Console,
+    Pipe(Arc<PipeEnd>),
+}
+
+#[derive(Debug)]
+pub struct FileTable {
+    files: Vec<Option<File>>,
+}
+
+impl FileTable {
+    pub fn new() -> Self {
+        Self { files: vec![Some(File::Console); 3] }
+    }
+
+    /// A table sharing every opened file of `self`, at the same file descriptors
+    pub fn inherit(&self) -> Self {
+        Self { files: self.files.clone() }
+    }
+
+    pub fn get(&self, fd: usize) -> Result<File, SyscallError> {
+        self.files.get(fd).cloned().flatten().ok_or(SyscallError::BadFileDescriptor)
+    }
+
+    /// Open `file` at the lowest free file descriptor, returns the file descriptor
+    pub fn insert(&mut self, file: File) -> Result<usize, SyscallError> {
+        if let Some(fd) = self.files.iter().position(Option::is_none) {
+            self.files[fd] = Some(file);
+            return Ok(fd);
+        }
+
+        if self.files.len() >= MAX_FILES {
+            return Err(SyscallError::TooManyFiles);
+        }
+
+        self.files.push(Some(file));
+        Ok(self.files.len() - 1)
+    }
+
+    /// Returns the closed file, it should be dropped after releasing the table lock since dropping
+    /// the last reference to a pipe end wakes up the other end
+    pub fn close(&mut self, fd: usize) -> Result<File, SyscallError> {
+        self.files.get_mut(fd).and_then(Option::take).ok_or(SyscallError::BadFileDescriptor)
+    }
+
+    pub fn dup(&mut self, fd: usize) -> Result<usize, SyscallError> {
+        let file = self.get(fd)?;
+        self.insert(file)
+    }
+
+    /// Close every file descriptor, see [`Self::close`]
+    pub fn close_all(&mut self) -> Vec<Option<File>> {
+        core::mem::take(&mut self.files)
+    }
+}
+
+impl Default for FileTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,127 @@
+//! Anonymous pipes, a bounded kernel buffer with a read end and a write end.
+//!
+//! A thread reading an empty pipe (or writing to a full one) is parked on the pipe, and its syscall
+//! is restarted once the other end makes progress (or gets closed). Each end is closed once every
+//! file descriptor referring to it is closed, which is when its [`PipeEnd`] is dropped.
+
+use alloc::{sync::Arc, vec::Vec};
+use hotline::SyscallError;
+use sink::singlethreaded::BoundedBuffer;
+use spin::Mutex;
+
+use crate::userland::pipeline::{TaskBlock, scheduler};
+
+/// The number of bytes a pipe can hold before the writers block
+pub const PIPE_CAPACITY: usize = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Read,
+    Write,
+}
+
+struct PipeState {
+    buffer: BoundedBuffer<u8, PIPE_CAPACITY>,
+    read_open: bool,
+    write_open: bool,
+    blocked_readers: Vec<TaskBlock>,
+    blocked_writers: Vec<TaskBlock>,
+}
+
+/// One end of a pipe, shared by every file descriptor duplicated from it
+pub struct PipeEnd {
+    state: Arc<Mutex<PipeState>>,
+    side: Side,
+}
+
+impl core::fmt::Debug for PipeEnd {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PipeEnd").field("side", &self.side).finish_non_exhaustive()
+    }
+}
+
+/// Create a new pipe, returns its read end and its write end
+pub fn pipe() -> (PipeEnd, PipeEnd) {
+    let state = Arc::new(Mutex::new(PipeState {
+        buffer: BoundedBuffer::new(),
+        read_open: true,
+        write_open: true,
+        blocked_readers: Vec::new(),
+        blocked_writers: Vec::new(),
+    }));
+
+    (PipeEnd { state: Arc::clone(&state), side: Side::Read }, PipeEnd { state, side: Side::Write })
+}
+
+impl PipeEnd {
+    /// Read the buffered bytes into `destination`, returns [`None`] if the pipe is empty, in which case
+    /// `task` is parked until a write, and must be blocked. Returns 0 once the write end is closed.
+    pub fn read(&self, task: TaskBlock, destination: &mut [u8]) -> Result<Option<usize>, SyscallError> {
+        if self.side != Side::Read {
+            return Err(SyscallError::BadFileDescriptor);
+        }
+
+        let mut state = self.state.lock();
+        if state.buffer.is_empty() {
+            if !state.write_open {
+                return Ok(Some(0));
+            }
+
+            state.blocked_readers.push(task);
+            return Ok(None);
+        }
+
+        let read = state.buffer.read(destination);
+        let writers = core::mem::take(&mut state.blocked_writers);
+        drop(state);
+
+        writers.into_iter().for_each(scheduler::resume);
+        Ok(Some(read))
+    }
+
+    /// Buffer as many bytes of `source` as there's room for, returns [`None`] if the pipe is full, in
+    /// which case `task` is parked until a read, and must be blocked.
+    pub fn write(&self, task: TaskBlock, source: &[u8]) -> Result<Option<usize>, SyscallError> {
+        if self.side != Side::Write {
+            return Err(SyscallError::BadFileDescriptor);
+        }
+
+        let mut state = self.state.lock();
+        if !state.read_open {
+            return Err(SyscallError::BrokenPipe);
+        }
+
+        if state.buffer.is_full() {
+            state.blocked_writers.push(task);
+            return Ok(None);
+        }
+
+        let written = state.buffer.write(source);
+        let readers = core::mem::take(&mut state.blocked_readers);
+        drop(state);
+
+        readers.into_iter().for_each(scheduler::resume);
+        Ok(Some(written))
+    }
+}
+
+impl Drop for PipeEnd {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+
+        // The parked threads of the other end retry, and see the closed end
+        let parked = match self.side {
+            Side::Read => {
+                state.read_open = false;
+                core::mem::take(&mut state.blocked_writers)
+            }
+            Side::Write => {
+                state.write_open = false;
+                core::mem::take(&mut state.blocked_readers)
+            }
+        };
+        drop(state);
+
+        parked.into_iter().for_each(scheduler::resume);
+    }
+}
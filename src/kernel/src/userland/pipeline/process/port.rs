@@ -0,0 +1,150 @@
+//! Message passing ports, a bounded kernel queue of fixed size messages.
+//!
+//! A port is reached through capabilities (See [`capability`](super::capability)), the process
+//! creating a port holds the only receive capability, and hands out send capabilities either by
+//! transferring them in a message or by spawning children. A thread receiving on an empty port is
+//! parked on the port, and its syscall is restarted once a message is sent (or the port is closed).
+//!
+//! A message can grant pages to the receiver, the pages are unmapped from the sender and their
+//! frames travel with the message until they're mapped in the receiver (See [`Grant`]).
+
+use alloc::{collections::vec_deque::VecDeque, sync::Arc, vec::Vec};
+use hotline::{MESSAGE_SIZE, SyscallError};
+use pager::{
+    address::{Frame, Size4K},
+    allocator::FrameAllocator,
+};
+use spin::Mutex;
+
+use crate::{
+    memory::BUDDY_ALLOCATOR,
+    userland::pipeline::{TaskBlock, scheduler},
+};
+
+use super::capability::Capability;
+
+/// The number of messages a port can hold before the senders get [`SyscallError::WouldBlock`]
+pub const PORT_QUEUE_SIZE: usize = 64;
+
+/// Physical frames moved from a process to another, the frames are freed if the grant is dropped
+/// before being mapped (e.g. the port got closed with the message still queued)
+#[derive(Debug)]
+pub struct Grant {
+    frames: Vec<Frame<Size4K>>,
+}
+
+impl Grant {
+    /// The frames must have been unmapped from every page table
+    pub fn new(frames: Vec<Frame<Size4K>>) -> Self {
+        Self { frames }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Take the frames out of the grant, the caller is now responsible for them
+    pub fn into_frames(mut self) -> Vec<Frame<Size4K>> {
+        core::mem::take(&mut self.frames)
+    }
+}
+
+impl Drop for Grant {
+    fn drop(&mut self) {
+        if self.frames.is_empty() {
+            return;
+        }
+
+        let mut allocator = BUDDY_ALLOCATOR.lock();
+        self.frames.drain(..).for_each(|frame| allocator.deallocate_frame(frame));
+    }
+}
+
+#[derive(Debug)]
+pub struct Message {
+    pub data: [u8; MESSAGE_SIZE],
+    /// The pid of the sending process
+    pub sender: usize,
+    pub grant: Option<Grant>,
+    /// A send capability transferred to the receiver
+    pub capability: Option<Capability>,
+}
+
+struct PortState {
+    queue: VecDeque<Message>,
+    blocked_receivers: Vec<TaskBlock>,
+    closed: bool,
+}
+
+pub struct Port {
+    state: Mutex<PortState>,
+}
+
+impl core::fmt::Debug for Port {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Port").finish_non_exhaustive()
+    }
+}
+
+impl Port {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(PortState { queue: VecDeque::new(), blocked_receivers: Vec::new(), closed: false }),
+        })
+    }
+
+    /// Queue the message built by `message`, it's only built once the port is known to be open and
+    /// to have room for it, so a failed send doesn't take anything (e.g. the granted pages) from the
+    /// sender. `message` is called with the port locked.
+    pub fn send(&self, message: impl FnOnce() -> Result<Message, SyscallError>) -> Result<(), SyscallError> {
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(SyscallError::PortClosed);
+        }
+
+        if state.queue.len() >= PORT_QUEUE_SIZE {
+            return Err(SyscallError::WouldBlock);
+        }
+
+        state.queue.push_back(message()?);
+        // Every receiver retries, a resumed receiver might have been killed while blocked
+        let receivers = core::mem::take(&mut state.blocked_receivers);
+        drop(state);
+
+        receivers.into_iter().for_each(scheduler::resume);
+        Ok(())
+    }
+
+    /// Dequeue the oldest message, returns [`None`] if the port is empty, in which case `task` is
+    /// parked until a send, and must be blocked
+    pub fn receive(&self, task: TaskBlock) -> Result<Option<Message>, SyscallError> {
+        let mut state = self.state.lock();
+        if let Some(message) = state.queue.pop_front() {
+            return Ok(Some(message));
+        }
+
+        if state.closed {
+            return Err(SyscallError::PortClosed);
+        }
+
+        state.blocked_receivers.push(task);
+        Ok(None)
+    }
+
+    /// Refuse any further message, returns the messages still queued, they should be dropped after
+    /// releasing any lock since dropping a grant frees its frames
+    pub fn close(&self) -> VecDeque<Message> {
+        let mut state = self.state.lock();
+        state.closed = true;
+        let queued = core::mem::take(&mut state.queue);
+        let receivers = core::mem::take(&mut state.blocked_receivers);
+        drop(state);
+
+        receivers.into_iter().for_each(scheduler::resume);
+        queued
+    }
+}
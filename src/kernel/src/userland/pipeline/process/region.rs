@@ -0,0 +1,272 @@
+//! Tracks the virtual memory regions a process allocated with the memory mapping syscalls.
+//!
+//! The tracker only deals with address ranges, the actual mapping is done by the
+//! [`ProcessPipeline`](super::ProcessPipeline). Regions never overlap each other, the user stack range
+//! starting at [`userland::STACK_START`], or the higher half.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use hotline::{Protection, SyscallError};
+use pager::{
+    EntryFlags, PAGE_SIZE,
+    address::{Page, PageIter, Size4K, VirtAddr},
+};
+
+use crate::userland;
+
+/// The end (exclusive) of the canonical lower half
+const LOWER_HALF_END: u64 = 0x0000_8000_0000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    start: VirtAddr,
+    /// Exclusive
+    end: VirtAddr,
+    protection: Protection,
+}
+
+impl Region {
+    pub fn start(&self) -> VirtAddr {
+        self.start
+    }
+
+    pub fn pages(&self) -> PageIter<Size4K> {
+        Page::range_inclusive(
+            Page::containing_address(self.start),
+            Page::containing_address(VirtAddr::new(self.end.as_u64() - 1)),
+        )
+    }
+
+    /// The page table flags matching the region protection
+    pub fn entry_flags(&self) -> EntryFlags {
+        if self.protection.is_empty() {
+            // The page stays mapped but only the kernel can access it
+            return EntryFlags::NO_EXECUTE;
+        }
+
+        let mut flags = EntryFlags::USER_ACCESSIBLE;
+        if self.protection.contains(Protection::WRITE) {
+            flags |= EntryFlags::WRITABLE;
+        }
+        if !self.protection.contains(Protection::EXECUTE) {
+            flags |= EntryFlags::NO_EXECUTE;
+        }
+        flags
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RegionTracker {
+    /// Keyed by the start of the region
+    regions: BTreeMap<VirtAddr, Region>,
+}
+
+impl RegionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve `length` bytes (rounded up to pages) at a fixed page aligned address
+    pub fn reserve(&mut self, start: VirtAddr, length: u64, protection: Protection) -> Result<Region, SyscallError> {
+        let end = checked_end(start, length)?;
+        if self.overlaps(start, end) {
+            return Err(SyscallError::AddressInUse);
+        }
+
+        let region = Region { start, end, protection };
+        self.regions.insert(start, region);
+        Ok(region)
+    }
+
+    /// Reserve `length` bytes (rounded up to pages) at the first free range after
+    /// [`userland::MMAP_START`] and below [`userland::STACK_START`]
+    pub fn reserve_any(&mut self, length: u64, protection: Protection) -> Result<Region, SyscallError> {
+        let size = round_up(length)?;
+        let mut candidate = userland::MMAP_START.as_u64();
+
+        for region in self.regions.values() {
+            if region.end.as_u64() <= candidate {
+                continue;
+            }
+
+            if region.start.as_u64() >= candidate && region.start.as_u64() - candidate >= size {
+                break;
+            }
+
+            candidate = region.end.as_u64();
+        }
+
+        if candidate.checked_add(size).is_none_or(|end| end > userland::STACK_START.as_u64()) {
+            return Err(SyscallError::OutOfMemory);
+        }
+
+        self.reserve(VirtAddr::new(candidate), size, protection)
+    }
+
+    /// Remove `length` bytes (rounded up to pages) starting at `start` from the tracker, the range
+    /// must be fully covered by regions. Returns the removed regions.
+    pub fn release(&mut self, start: VirtAddr, length: u64) -> Result<Vec<Region>, SyscallError> {
+        let end = checked_end(start, length)?;
+        if !self.covers(start, end) {
+            return Err(SyscallError::BadAddress);
+        }
+
+        self.split_at(start);
+        self.split_at(end);
+
+        let mut released = self.regions.split_off(&start);
+        let mut after = released.split_off(&end);
+        self.regions.append(&mut after);
+
+        Ok(released.into_values().collect())
+    }
+
+    /// Change the protection of `length` bytes (rounded up to pages) starting at `start`, the range
+    /// must be fully covered by regions. Returns the whole changed range as a single region.
+    pub fn protect(&mut self, start: VirtAddr, length: u64, protection: Protection) -> Result<Region, SyscallError> {
+        let end = checked_end(start, length)?;
+        if !self.covers(start, end) {
+            return Err(SyscallError::BadAddress);
+        }
+
+        self.split_at(start);
+        self.split_at(end);
+
+        for region in self.regions.range_mut(start..end).map(|(_, region)| region) {
+            region.protection = protection;
+        }
+
+        Ok(Region { start, end, protection })
+    }
+
+    /// The number of pages covered by the regions
+    pub fn mapped_pages(&self) -> usize {
+        self.regions.values().map(|region| ((region.end.as_u64() - region.start.as_u64()) / PAGE_SIZE) as usize).sum()
+    }
+
+    fn overlaps(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        // Regions never overlap, so the last region starting before `end` is the one reaching the furthest
+        self.regions.range(..end).next_back().is_some_and(|(_, region)| region.end > start)
+    }
+
+    fn covers(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        let mut cursor = match self.regions.range(..=start).next_back() {
+            Some((_, region)) if region.end > start => region.end,
+            _ => return false,
+        };
+
+        while cursor < end {
+            match self.regions.get(&cursor) {
+                Some(region) => cursor = region.end,
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Split the region containing `at` (if any) into two regions, one ending and one starting at `at`
+    fn split_at(&mut self, at: VirtAddr) {
+        let Some((_, region)) = self.regions.range_mut(..at).next_back() else {
+            return;
+        };
+
+        if region.end <= at {
+            return;
+        }
+
+        let tail = Region { start: at, end: region.end, protection: region.protection };
+        region.end = at;
+        self.regions.insert(at, tail);
+    }
+}
+
+fn round_up(length: u64) -> Result<u64, SyscallError> {
+    match length.checked_next_multiple_of(PAGE_SIZE) {
+        Some(0) | None => Err(SyscallError::InvalidArgument),
+        Some(size) => Ok(size),
+    }
+}
+
+/// Validate the range and return its end (exclusive)
+fn checked_end(start: VirtAddr, length: u64) -> Result<VirtAddr, SyscallError> {
+    if !start.as_u64().is_multiple_of(PAGE_SIZE) {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let end = start.as_u64().checked_add(round_up(length)?).ok_or(SyscallError::BadAddress)?;
+    let stack_start = userland::STACK_START.as_u64();
+    let stack_end = stack_start + userland::STACK_MAX_SIZE as u64;
+
+    if end > LOWER_HALF_END || (start.as_u64() <= stack_end && end > stack_start) {
+        return Err(SyscallError::BadAddress);
+    }
+
+    Ok(VirtAddr::new(end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RW: Protection = Protection::READ.union(Protection::WRITE);
+
+    fn at(page: u64) -> VirtAddr {
+        userland::MMAP_START + page * PAGE_SIZE
+    }
+
+    #[test_case]
+    fn region_reserve_and_release() {
+        let mut tracker = RegionTracker::new();
+        let first = tracker.reserve_any(PAGE_SIZE + 1, RW).unwrap();
+        assert_eq!((first.start(), first.pages().count()), (at(0), 2));
+        assert_eq!(tracker.reserve_any(PAGE_SIZE, RW).unwrap().start(), at(2));
+        assert_eq!(tracker.mapped_pages(), 3);
+
+        assert_eq!(tracker.release(at(0), 2 * PAGE_SIZE).unwrap(), [first]);
+        assert_eq!(tracker.release(at(0), PAGE_SIZE), Err(SyscallError::BadAddress));
+
+        // The freed range is reused when it's large enough
+        assert_eq!(tracker.reserve_any(PAGE_SIZE, RW).unwrap().start(), at(0));
+        assert_eq!(tracker.reserve_any(2 * PAGE_SIZE, RW).unwrap().start(), at(3));
+    }
+
+    #[test_case]
+    fn region_overlap() {
+        let mut tracker = RegionTracker::new();
+        tracker.reserve(at(4), 4 * PAGE_SIZE, RW).unwrap();
+
+        assert_eq!(tracker.reserve(at(2), 3 * PAGE_SIZE, RW), Err(SyscallError::AddressInUse));
+        assert_eq!(tracker.reserve(at(7), PAGE_SIZE, RW), Err(SyscallError::AddressInUse));
+        assert_eq!(tracker.reserve(at(5), PAGE_SIZE, RW), Err(SyscallError::AddressInUse));
+        assert!(tracker.reserve(at(2), 2 * PAGE_SIZE, RW).is_ok());
+        assert!(tracker.reserve(at(8), PAGE_SIZE, RW).is_ok());
+
+        assert_eq!(tracker.reserve(at(0) + 1u64, PAGE_SIZE, RW), Err(SyscallError::InvalidArgument));
+        assert_eq!(tracker.reserve(at(0), 0, RW), Err(SyscallError::InvalidArgument));
+        assert_eq!(tracker.reserve(userland::STACK_START, PAGE_SIZE, RW), Err(SyscallError::BadAddress));
+    }
+
+    #[test_case]
+    fn region_split() {
+        let mut tracker = RegionTracker::new();
+        tracker.reserve(at(0), 4 * PAGE_SIZE, RW).unwrap();
+
+        // Releasing the middle of a region keeps both ends
+        let released = tracker.release(at(1), 2 * PAGE_SIZE).unwrap();
+        assert_eq!(released, [Region { start: at(1), end: at(3), protection: RW }]);
+        assert_eq!(tracker.mapped_pages(), 2);
+        assert_eq!(tracker.release(at(0), 4 * PAGE_SIZE), Err(SyscallError::BadAddress));
+
+        // Protecting across adjacent regions splits them at the range boundaries
+        tracker.reserve(at(1), 2 * PAGE_SIZE, RW).unwrap();
+        assert_eq!(tracker.protect(at(0), 0, Protection::READ), Err(SyscallError::InvalidArgument));
+        let protected = tracker.protect(at(0), 2 * PAGE_SIZE, Protection::READ).unwrap();
+        assert_eq!(protected.pages().count(), 2);
+        assert_eq!(protected.entry_flags(), EntryFlags::USER_ACCESSIBLE | EntryFlags::NO_EXECUTE);
+
+        let released = tracker.release(at(0), 4 * PAGE_SIZE).unwrap();
+        let protections = released.iter().map(|region| (region.start(), region.protection)).collect::<Vec<_>>();
+        assert_eq!(protections, [(at(0), Protection::READ), (at(1), Protection::READ), (at(2), RW), (at(3), RW)]);
+        assert_eq!(tracker.mapped_pages(), 0);
+    }
+}
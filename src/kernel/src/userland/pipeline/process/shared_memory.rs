@@ -0,0 +1,140 @@
+//! Shared memory objects, a set of physical frames that can be mapped by several processes at once.
+//!
+//! An object is reached through a capability (See [`capability`](super::capability)), and every
+//! mapping of it keeps a reference to it, so the frames are freed once the last capability is
+//! dropped and the last mapping is unmapped.
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use hotline::SyscallError;
+use pager::{
+    KERNEL_DIRECT_PHYSICAL_MAP, PAGE_SIZE,
+    address::{Frame, Size4K, VirtAddr},
+    allocator::FrameAllocator,
+};
+
+use crate::memory::BUDDY_ALLOCATOR;
+
+/// The maximum size of a shared memory object, the frames are allocated (and zeroed) up front
+pub const MAX_SHARED_MEMORY_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct SharedMemory {
+    frames: Vec<Frame<Size4K>>,
+}
+
+impl SharedMemory {
+    /// Allocate `length` bytes (rounded up to pages) of zeroed memory, at most [`MAX_SHARED_MEMORY_SIZE`]
+    pub fn new(length: u64) -> Result<Arc<Self>, SyscallError> {
+        if length == 0 || length > MAX_SHARED_MEMORY_SIZE {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        let count = length.div_ceil(PAGE_SIZE) as usize;
+        let mut frames = Vec::with_capacity(count);
+        let mut allocator = BUDDY_ALLOCATOR.lock();
+        for _ in 0..count {
+            let Some(frame) = allocator.allocate_frame() else {
+                frames.into_iter().for_each(|frame| allocator.deallocate_frame(frame));
+                return Err(SyscallError::OutOfMemory);
+            };
+
+            // SAFETY: The allocator only hands out conventional memory, which is mapped in the direct
+            // physical map, and the frame isn't referenced by anything yet
+            unsafe {
+                let address = VirtAddr::new(KERNEL_DIRECT_PHYSICAL_MAP.as_u64() + frame.start_address().as_u64());
+                core::ptr::write_bytes(address.as_mut_ptr::<u8>(), 0, PAGE_SIZE as usize);
+            }
+            frames.push(frame);
+        }
+
+        Ok(Arc::new(Self { frames }))
+    }
+
+    pub fn frames(&self) -> &[Frame<Size4K>] {
+        &self.frames
+    }
+
+    /// The size of the object in bytes, a multiple of the page size
+    pub fn length(&self) -> u64 {
+        self.frames.len() as u64 * PAGE_SIZE
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        let mut allocator = BUDDY_ALLOCATOR.lock();
+        self.frames.drain(..).for_each(|frame| allocator.deallocate_frame(frame));
+    }
+}
+
+/// The shared memory objects mapped by a process, a mapping always covers a whole object and can
+/// only be unmapped as a whole. The mapped ranges are also reserved in the region tracker.
+#[derive(Debug, Default)]
+pub struct SharedMappings {
+    /// Keyed by the start of the mapping
+    mappings: BTreeMap<VirtAddr, Arc<SharedMemory>>,
+}
+
+impl SharedMappings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, start: VirtAddr, memory: Arc<SharedMemory>) {
+        self.mappings.insert(start, memory);
+    }
+
+    /// Returns true if a mapping overlaps `length` bytes (rounded up to pages) starting at `start`
+    pub fn overlaps(&self, start: VirtAddr, length: u64) -> bool {
+        self.overlapping(start, length).next().is_some()
+    }
+
+    /// Fails if a mapping is only partially covered by `length` bytes (rounded up to pages) starting
+    /// at `start`
+    pub fn check_unmap(&self, start: VirtAddr, length: u64) -> Result<(), SyscallError> {
+        let end = range_end(start, length);
+        let covered = |(mapping, memory): (&VirtAddr, &Arc<SharedMemory>)| {
+            mapping.as_u64() >= start.as_u64() && end_of(*mapping, memory) <= end
+        };
+
+        if !self.overlapping(start, length).all(covered) {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Remove the mappings overlapping `length` bytes (rounded up to pages) starting at `start`, See
+    /// [`Self::check_unmap`]. The objects should be dropped after releasing any lock, since dropping
+    /// the last reference frees the frames
+    pub fn remove(&mut self, start: VirtAddr, length: u64) -> Vec<(VirtAddr, Arc<SharedMemory>)> {
+        let starts: Vec<_> = self.overlapping(start, length).map(|(mapping, _)| *mapping).collect();
+        starts.into_iter().filter_map(|start| self.mappings.remove_entry(&start)).collect()
+    }
+
+    /// Remove every mapping, See [`Self::remove`]
+    pub fn remove_all(&mut self) -> Vec<(VirtAddr, Arc<SharedMemory>)> {
+        core::mem::take(&mut self.mappings).into_iter().collect()
+    }
+
+    fn overlapping(&self, start: VirtAddr, length: u64) -> impl Iterator<Item = (&VirtAddr, &Arc<SharedMemory>)> {
+        let end = range_end(start, length);
+        self.mappings
+            .iter()
+            .filter(move |(mapping, memory)| mapping.as_u64() < end && end_of(**mapping, memory) > start.as_u64())
+    }
+}
+
+/// Whether `address` falls in one of the `mappings`
+pub fn contains(mappings: &[(VirtAddr, Arc<SharedMemory>)], address: VirtAddr) -> bool {
+    mappings.iter().any(|(start, memory)| address >= *start && address.as_u64() < end_of(*start, memory))
+}
+
+/// The end (exclusive) of a mapping, as a raw address since it can be past the lower half
+fn end_of(start: VirtAddr, memory: &SharedMemory) -> u64 {
+    start.as_u64() + memory.length()
+}
+
+fn range_end(start: VirtAddr, length: u64) -> u64 {
+    start.as_u64().saturating_add(length.saturating_add(PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE)
+}
@@ -0,0 +1,118 @@
+//! TLB shootdown of the other cores running a process
+//!
+//! Every core has its own copy of the page table of a process, but the copies share the p3 tables so
+//! a mapping changed on one core is seen by all of them, only the translations the other cores already
+//! cached stay stale. A frame unmapped from a process can't be freed (or given to another process)
+//! until every core that has the process page table loaded flushed its TLB, see [`ActiveCores`].
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering, fence};
+
+use alloc::{sync::Arc, vec::Vec};
+use kernel_proc::IPPacket;
+use pager::{
+    address::{AnyFrame, Frame, PageSize, Size4K},
+    allocator::FrameAllocator,
+    paging::mapper::MapperWithAllocator,
+    registers::tlb,
+};
+
+use crate::{
+    interrupt::CORE_ID,
+    memory::mapper_lower,
+    smp::{CoreId, MAX_CPU},
+};
+
+/// The cores with the page table of a process loaded, a core is added before loading the table and
+/// removed once it's unloaded (which flushes the TLB)
+#[derive(Default)]
+pub struct ActiveCores(AtomicU64);
+
+impl ActiveCores {
+    pub fn insert(&self) {
+        self.0.fetch_or(1 << CORE_ID.id(), Ordering::SeqCst);
+    }
+
+    pub fn remove(&self) {
+        self.0.fetch_and(!(1 << CORE_ID.id()), Ordering::SeqCst);
+    }
+
+    /// Flush the TLB of every other core in the set, returns once all of them did. Must be called after
+    /// the mappings changed, without holding the page table modification lock of the process
+    pub fn shootdown(&self) {
+        // Pairs with the core added in insert, either the core is seen here or it loads the page table
+        // after the mappings changed
+        fence(Ordering::SeqCst);
+        let cores = self.0.load(Ordering::SeqCst) & !(1 << CORE_ID.id());
+        if cores == 0 {
+            return;
+        }
+
+        let pending = Arc::new(AtomicUsize::new(cores.count_ones() as usize));
+        for core in (0..MAX_CPU).filter(|core| cores & 1 << core != 0).filter_map(CoreId::new) {
+            ShootdownPacket { pending: Arc::clone(&pending) }.send(core, false);
+        }
+
+        // The other cores might be waiting for this one to flush with their interrupts disabled
+        while pending.load(Ordering::Acquire) != 0 {
+            handle();
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Flush the TLB for every shootdown sent to this core
+pub fn handle() {
+    ShootdownPacket::handle(|packet| {
+        tlb::full_flush();
+        packet.pending.fetch_sub(1, Ordering::Release);
+    });
+}
+
+/// A frame allocator that only collects the freed frames, so the mapper can unmap pages without
+/// freeing their frames before the shootdown
+#[derive(Default)]
+pub struct DeferredFrames(Vec<AnyFrame>);
+
+impl DeferredFrames {
+    /// Free the collected frames, once no core can access them anymore
+    pub fn free(self) {
+        mapper_lower(|MapperWithAllocator { allocator, .. }| {
+            self.0.into_iter().for_each(|frame| allocator.deallocate_frame_any(frame))
+        });
+    }
+}
+
+// SAFETY: Nothing is ever allocated
+unsafe impl FrameAllocator for DeferredFrames {
+    fn allocate_frame<S: PageSize>(&mut self) -> Option<Frame<S>> {
+        None
+    }
+
+    fn deallocate_frame<S: PageSize>(&mut self, frame: Frame<S>) {
+        self.0.push(frame.erase_level());
+    }
+}
+
+/// Frames freed once every core dropped its reference, for the tables that are still linked in the page
+/// tables of the other cores
+pub struct ReleasedFrames(Vec<Frame<Size4K>>);
+
+impl ReleasedFrames {
+    pub fn new(frames: Vec<Frame<Size4K>>) -> Self {
+        Self(frames)
+    }
+}
+
+impl Drop for ReleasedFrames {
+    fn drop(&mut self) {
+        mapper_lower(|MapperWithAllocator { allocator, .. }| {
+            self.0.drain(..).for_each(|frame| allocator.deallocate_frame(frame))
+        });
+    }
+}
+
+#[derive(IPPacket)]
+struct ShootdownPacket {
+    /// The number of cores that didn't flush yet
+    pending: Arc<AtomicUsize>,
+}
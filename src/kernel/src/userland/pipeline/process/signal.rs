@@ -0,0 +1,251 @@
+//! Signals, asynchronous notifications sent to a process or to one of its threads.
+//!
+//! The default action of a signal (terminating the process or ignoring the signal) is taken as soon
+//! as it's sent, only the signals with a user handler are queued. A queued signal is delivered when a
+//! thread it targets returns to userland from a request (a syscall or an interrupt): the state of the
+//! thread is saved on its stack (See [`save`]) and the thread resumes at the trampoline registered
+//! along the handler, which gives the state back with `signal_return` (See [`restore`]).
+//!
+//! A thread blocked in a syscall doesn't take its signals until it's woken up.
+//!
+//! The extended state (the floating point and vector registers) follows the registers in the frame,
+//! so the handler is free to change it.
+
+use core::num::NonZeroUsize;
+
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use hotline::{SIGNAL_DEFAULT, SIGNAL_IGNORE, Signal, SyscallError};
+use pager::{address::VirtAddr, registers::RFlags};
+
+use crate::userland::pipeline::{TaskProcesserState, thread::extended_state::ExtendedState};
+
+/// The general purpose registers, the instruction pointer, the flags and the stack pointer
+const FRAME_REGISTERS: usize = 18;
+const REGISTERS_SIZE: usize = FRAME_REGISTERS * size_of::<u64>();
+
+/// The bytes below the stack pointer the interrupted code might be using (the System V red zone)
+const RED_ZONE_SIZE: u64 = 128;
+
+/// The flags a thread is allowed to restore with `signal_return`
+const USER_FLAGS: RFlags = RFlags::Carry
+    .union(RFlags::ParityFlag)
+    .union(RFlags::AuxiliaryCarry)
+    .union(RFlags::Zero)
+    .union(RFlags::Sign)
+    .union(RFlags::Direction)
+    .union(RFlags::Overflow)
+    .union(RFlags::AlignmentCheck)
+    .union(RFlags::ID);
+
+/// What a process does when it receives a signal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Disposition {
+    #[default]
+    Default,
+    Ignore,
+    Handler {
+        handler: VirtAddr,
+        trampoline: VirtAddr,
+    },
+}
+
+impl Disposition {
+    /// Decode the handler and the trampoline passed to `signal_action`
+    pub fn new(handler: usize, trampoline: usize) -> Result<Self, SyscallError> {
+        match handler {
+            SIGNAL_DEFAULT => Ok(Self::Default),
+            SIGNAL_IGNORE => Ok(Self::Ignore),
+            handler => Ok(Self::Handler {
+                handler: user_address(handler as u64)?,
+                trampoline: user_address(trampoline as u64)?,
+            }),
+        }
+    }
+
+    /// The handler as passed to `signal_action`
+    pub fn raw_handler(&self) -> usize {
+        match self {
+            Self::Default => SIGNAL_DEFAULT,
+            Self::Ignore => SIGNAL_IGNORE,
+            Self::Handler { handler, .. } => handler.as_u64() as usize,
+        }
+    }
+}
+
+/// A signal taken from the queue of a thread, See [`SignalState::take`]
+#[derive(Debug, Clone, Copy)]
+pub enum Delivery {
+    /// The handler was reset to the default action since the signal was sent
+    Terminate,
+    Handle {
+        signal: Signal,
+        handler: VirtAddr,
+        trampoline: VirtAddr,
+    },
+}
+
+/// A set of pending signals
+#[derive(Debug, Clone, Copy, Default)]
+struct SignalSet(u64);
+
+impl SignalSet {
+    fn insert(&mut self, signal: Signal) {
+        self.0 |= 1 << signal as u64;
+    }
+
+    /// Remove the lowest signal of the set
+    fn pop(&mut self) -> Option<Signal> {
+        let signal = Signal::ALL.get(self.0.trailing_zeros() as usize).copied()?;
+        self.0 &= !(1 << signal as u64);
+        Some(signal)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SignalState {
+    dispositions: [Disposition; Signal::COUNT],
+    /// The signals sent to the whole process
+    pending: SignalSet,
+    /// The signals sent to a single thread, keyed on the thread id
+    thread_pending: HashMap<NonZeroUsize, SignalSet>,
+}
+
+impl SignalState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what the process does when it receives `signal`, returns the previous disposition
+    pub fn set_disposition(&mut self, signal: Signal, disposition: Disposition) -> Result<Disposition, SyscallError> {
+        if signal == Signal::Kill {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        Ok(core::mem::replace(&mut self.dispositions[signal as usize], disposition))
+    }
+
+    /// Queue `signal` for the process, or for its thread `thread`, unless it's ignored. Returns true
+    /// if the process must be terminated right away
+    pub fn send(&mut self, signal: Signal, thread: Option<NonZeroUsize>) -> bool {
+        // The disposition of Kill is never changed from the default
+        match self.dispositions[signal as usize] {
+            Disposition::Handler { .. } => {
+                match thread {
+                    Some(thread) => self.thread_pending.entry(thread).or_default().insert(signal),
+                    None => self.pending.insert(signal),
+                }
+                false
+            }
+            Disposition::Ignore => false,
+            Disposition::Default => signal.terminates_by_default(),
+        }
+    }
+
+    /// Take the next signal the thread `thread` must handle, the signals sent to the thread come first
+    pub fn take(&mut self, thread: NonZeroUsize) -> Option<Delivery> {
+        loop {
+            let thread_signal = self.thread_pending.get_mut(&thread).and_then(SignalSet::pop);
+            let signal = thread_signal.or_else(|| self.pending.pop())?;
+
+            match self.dispositions[signal as usize] {
+                Disposition::Handler { handler, trampoline } => {
+                    return Some(Delivery::Handle { signal, handler, trampoline });
+                }
+                Disposition::Default if signal.terminates_by_default() => return Some(Delivery::Terminate),
+                _ => {}
+            }
+        }
+    }
+
+    /// Drop the signals queued for the thread `thread`, once it exited
+    pub fn remove_thread(&mut self, thread: NonZeroUsize) {
+        self.thread_pending.remove(&thread);
+    }
+}
+
+/// The size of the state saved on the stack of a thread running a signal handler, the registers
+/// followed by the extended state
+pub fn frame_size() -> usize {
+    REGISTERS_SIZE + ExtendedState::size()
+}
+
+/// Where the state of a thread running on `stack_pointer` is saved, below its red zone
+pub fn frame_address(stack_pointer: VirtAddr) -> Option<VirtAddr> {
+    let frame = stack_pointer.as_u64().checked_sub(RED_ZONE_SIZE + frame_size() as u64)? & !0xF;
+    user_address(frame).ok()
+}
+
+/// Serialize the state of a thread to be saved on its stack along its `extended_state` (See
+/// [`ExtendedState::to_bytes`]), the layout is private to the kernel
+pub fn save(state: &TaskProcesserState, extended_state: &[u8]) -> Vec<u8> {
+    let registers: [u64; FRAME_REGISTERS] = [
+        state.r15,
+        state.r14,
+        state.r13,
+        state.r12,
+        state.r11,
+        state.r10,
+        state.r9,
+        state.r8,
+        state.rsi,
+        state.rdi,
+        state.rbp,
+        state.rdx,
+        state.rcx,
+        state.rbx,
+        state.rax,
+        state.instruction_pointer.as_u64(),
+        state.cpu_flags.bits(),
+        state.stack_pointer.as_u64(),
+    ];
+
+    let mut frame = Vec::with_capacity(REGISTERS_SIZE + extended_state.len());
+    registers.iter().for_each(|register| frame.extend_from_slice(&register.to_ne_bytes()));
+    frame.extend_from_slice(extended_state);
+    frame
+}
+
+/// Restore a state serialized by [`save`], the frame comes from the user so the instruction pointer
+/// and the stack pointer must be user addresses, and only the [`USER_FLAGS`] are restored. Returns
+/// the extended state of the frame, see [`ExtendedState::set_bytes`]
+pub fn restore<'a>(state: &mut TaskProcesserState, frame: &'a [u8]) -> Result<&'a [u8], SyscallError> {
+    let (frame, extended_state) = frame.split_at(REGISTERS_SIZE);
+    let mut registers = [0; FRAME_REGISTERS];
+    for (register, chunk) in registers.iter_mut().zip(frame.chunks_exact(size_of::<u64>())) {
+        *register = u64::from_ne_bytes(chunk.try_into().expect("The chunks are 8 bytes long"));
+    }
+
+    let [r15, r14, r13, r12, r11, r10, r9, r8, rsi, rdi, rbp, rdx, rcx, rbx, rax, rip, flags, rsp] = registers;
+    *state = TaskProcesserState {
+        r15,
+        r14,
+        r13,
+        r12,
+        r11,
+        r10,
+        r9,
+        r8,
+        rsi,
+        rdi,
+        rbp,
+        rdx,
+        rcx,
+        rbx,
+        rax,
+        instruction_pointer: user_address(rip)?,
+        cpu_flags: RFlags::from_bits_truncate(flags) & USER_FLAGS | RFlags::InterruptEnable,
+        stack_pointer: user_address(rsp)?,
+        extended_state: core::mem::take(&mut state.extended_state),
+    };
+
+    Ok(extended_state)
+}
+
+/// A canonical lower half address, anything else can't be returned to with `iretq`
+fn user_address(address: u64) -> Result<VirtAddr, SyscallError> {
+    VirtAddr::new_checked(address)
+        .ok()
+        .filter(|address| !address.is_canonical_higher_half())
+        .ok_or(SyscallError::BadAddress)
+}
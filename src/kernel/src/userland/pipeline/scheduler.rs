@@ -1,37 +1,278 @@
+//! A multilevel feedback queue scheduler, one per core.
+//!
+//! A thread starts at the level given by its nice value, and sinks a level each time it runs for its
+//! whole time slice (longer at the lower levels), so the threads mostly waiting on something keep
+//! running before the cpu bound ones. A thread moves back to its base level when it's woken up from
+//! a sleep or a blocking syscall, and every ready thread is moved to the top level every
+//! [`BOOST_INTERVAL_MS`] so neither the sunk threads nor the threads with a higher nice value can starve.
+//!
+//! The running thread keeps the cpu across requests until its time slice runs out, or a thread of a
+//! higher level is ready.
+//!
+//! The local APIC timer doesn't tick, it's armed in one-shot mode for the end of the time slice of the
+//! running thread or the first sleeping thread to wake up, whichever comes first (See [`Timer`]). A
+//! thread running alone on its core is interrupted once its time slice runs out too, and an idle core
+//! isn't woken up until it's sent a thread or one of its threads must wake up.
+
 use core::{
-    cmp::Reverse,
+    hash::Hash,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use alloc::collections::{binary_heap::BinaryHeap, vec_deque::VecDeque};
+use alloc::{
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    vec::Vec,
+};
 use config::config;
-use derivative::Derivative;
+use hashbrown::HashMap;
+use hotline::{DEFAULT_NICE, MAX_NICE, MIN_NICE, SyscallError};
+use kernel_proc::IPPacket;
+use pager::address::VirtAddr;
 
 use crate::{
-    interrupt::{CORE_ID, InterruptIndex, LAPIC},
+    interrupt::{CORE_ID, InterruptIndex, LAPIC, TPMS},
     smp::{CoreId, MAX_CPU},
+    time::{self, NANOS_PER_MILLI},
     userland::{
-        pipeline::{Event, PipelineContext, TaskBlock, thread::ThreadPipeline},
+        pipeline::{Event, PipelineContext, TaskBlock, process::ProcessPipeline, thread::ThreadPipeline},
         syscall::MIGRATE_COUNT,
     },
 };
 
 const MIGRATION_THRESHOLD: usize = 2;
 
-#[derive(Derivative)]
-#[derivative(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct SleepEntry {
-    wakeup_time: usize,
-    #[derivative(PartialEq = "ignore", PartialOrd = "ignore", Ord = "ignore")]
-    task: TaskBlock,
+/// The number of feedback queues, the level 0 is scheduled first
+const LEVELS: usize = 8;
+
+/// How often every ready thread is moved to the top level
+const BOOST_INTERVAL_MS: usize = 1000;
+
+/// The scheduling state of a thread, it moves along the thread when it's migrated
+#[derive(Debug, Clone, Copy)]
+pub struct Scheduling {
+    nice: i8,
+    /// The level the thread is queued at
+    level: usize,
+    /// The milliseconds the thread ran for since it reached its level
+    used_ms: usize,
+}
+
+impl Default for Scheduling {
+    fn default() -> Self {
+        let mut scheduling = Self { nice: DEFAULT_NICE, level: 0, used_ms: 0 };
+        scheduling.level = scheduling.base_level();
+        scheduling
+    }
+}
+
+impl Scheduling {
+    /// Set the nice value, from [`MIN_NICE`] to [`MAX_NICE`], the thread keeps the levels it sunk below
+    /// its base level
+    pub fn set_nice(&mut self, nice: i8) -> Result<(), SyscallError> {
+        if !(MIN_NICE..=MAX_NICE).contains(&nice) {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        let penalty = self.level.saturating_sub(self.base_level());
+        self.nice = nice;
+        self.level = (self.base_level() + penalty).min(LEVELS - 1);
+        Ok(())
+    }
+
+    /// The level the thread starts at, the nice values are spread evenly across the levels
+    fn base_level(&self) -> usize {
+        (self.nice - MIN_NICE) as usize * LEVELS / (MAX_NICE - MIN_NICE + 1) as usize
+    }
+
+    fn level(&self) -> usize {
+        self.level
+    }
+
+    /// The milliseconds the thread runs for before sinking a level, a timer tick per level
+    fn time_slice_ms(&self) -> usize {
+        (self.level() + 1) * timer_ms()
+    }
+
+    fn remaining_ms(&self) -> usize {
+        self.time_slice_ms().saturating_sub(self.used_ms)
+    }
+
+    /// Charge the time the thread ran for, returns true if its time slice ran out, the thread then
+    /// sinks a level
+    fn charge(&mut self, ms: usize) -> bool {
+        self.used_ms += ms;
+        if self.used_ms < self.time_slice_ms() {
+            return false;
+        }
+
+        self.used_ms = 0;
+        self.level = (self.level + 1).min(LEVELS - 1);
+        true
+    }
+
+    /// Move the thread back to its base level with a fresh time slice
+    pub fn boost(&mut self) {
+        self.level = self.base_level();
+        self.used_ms = 0;
+    }
+
+    /// Move the thread to the top level with a fresh time slice, whatever its nice value
+    fn lift(&mut self) {
+        self.level = 0;
+        self.used_ms = 0;
+    }
 }
 
+/// The local APIC timer of the core armed in one-shot mode, and the timer count (the milliseconds
+/// elapsed on the core) kept along it
 #[derive(Debug, Default)]
-pub struct SchedulerPipeline {
-    units: VecDeque<TaskBlock>,
-    sleep_queue: BinaryHeap<Reverse<SleepEntry>>,
+struct Timer {
+    count: usize,
+    /// The timer count the timer fires at, [`None`] when there's nothing to wake up for
+    deadline: Option<usize>,
+    /// The initial count the timer was last armed with
+    armed_ticks: usize,
+    /// The timer ticks elapsed since it was armed, already added to the count
+    counted_ticks: usize,
+    /// The TSC at the last update, 0 until the count is kept from the TSC
+    last_tsc: u64,
+    /// The elapsed ticks (of the TSC or of the timer) below a millisecond, not added to the count yet
+    remainder: u64,
+}
+
+impl Timer {
+    /// Add the time elapsed since the previous update to the count, it's read from the TSC when it's
+    /// usable (the timer is then stopped when there's no deadline), otherwise from the timer itself
+    fn update(&mut self) {
+        let (elapsed, ticks_per_ms) = match time::tsc_per_ms() {
+            Some(tsc_per_ms) => {
+                let tsc = time::rdtsc();
+                let last_tsc = core::mem::replace(&mut self.last_tsc, tsc);
+                (if last_tsc == 0 { 0 } else { tsc.saturating_sub(last_tsc) }, tsc_per_ms)
+            }
+            None => {
+                let ticks = self.armed_ticks.saturating_sub(LAPIC.inner_mut().current_count());
+                let elapsed = ticks.saturating_sub(self.counted_ticks);
+                self.counted_ticks = ticks;
+                (elapsed as u64, *TPMS as u64)
+            }
+        };
+
+        self.remainder += elapsed;
+        self.count += (self.remainder / ticks_per_ms) as usize;
+        self.remainder %= ticks_per_ms;
+    }
 
-    timer_count: usize,
+    /// The timer count `millis` milliseconds from now, rounded up so it's never reached early
+    fn after(&self, millis: usize) -> usize {
+        self.count.saturating_add(millis).saturating_add(usize::from(self.remainder != 0))
+    }
+
+    /// Arm the timer to fire once at `deadline`, or stop it when there's none
+    fn arm(&mut self, deadline: Option<usize>) {
+        self.update();
+        self.deadline = deadline;
+
+        let timer_per_ms = *TPMS as u64;
+        let tsc_per_ms = time::tsc_per_ms();
+        let ticks = match deadline {
+            Some(deadline) => {
+                let below_milli =
+                    tsc_per_ms.map_or(self.remainder, |tsc_per_ms| self.remainder * timer_per_ms / tsc_per_ms);
+                (deadline.saturating_sub(self.count) as u64)
+                    .saturating_mul(timer_per_ms)
+                    .saturating_sub(below_milli)
+                    .max(1)
+            }
+            None if tsc_per_ms.is_some() => 0,
+            // The count is kept from the timer, it can't be stopped
+            None => u64::MAX,
+        };
+
+        // A deadline too far to be armed at once is armed again when the timer fires
+        let ticks = ticks.min(u32::MAX as u64) as usize;
+        LAPIC.inner_mut().reset_timer(ticks);
+        self.armed_ticks = ticks;
+        self.counted_ticks = 0;
+    }
+}
+
+/// What happens once a sleep entry expires
+#[derive(Debug, Clone, Copy)]
+pub enum Timeout {
+    /// The task is woken up
+    Sleep,
+    /// The task is blocked on the futex at `address`, it's dequeued and woken up with
+    /// [`SyscallError::TimedOut`]
+    Futex { address: VirtAddr },
+}
+
+/// The sleep entries of a core ordered by wakeup time, an entry is removed as soon as it's cancelled
+#[derive(Debug)]
+struct SleepQueue<T> {
+    /// Keyed on the wakeup time, and on an id so the entries of the same time expire in order
+    entries: BTreeMap<(usize, u64), (T, Timeout)>,
+    /// The key of the entry of every sleeping task
+    keys: HashMap<T, (usize, u64)>,
+    next_id: u64,
+}
+
+impl<T> Default for SleepQueue<T> {
+    fn default() -> Self {
+        Self { entries: BTreeMap::new(), keys: HashMap::new(), next_id: 0 }
+    }
+}
+
+impl<T: Copy + Eq + Hash> SleepQueue<T> {
+    /// Queue an entry for `task` expiring at `wakeup_time`, replacing its previous entry
+    fn insert(&mut self, task: T, wakeup_time: usize, timeout: Timeout) {
+        let key = (wakeup_time, self.next_id);
+        self.next_id += 1;
+
+        if let Some(previous) = self.keys.insert(task, key) {
+            self.entries.remove(&previous);
+        }
+        self.entries.insert(key, (task, timeout));
+    }
+
+    /// Remove the entry of `task`, returns false if it didn't have one
+    fn cancel(&mut self, task: T) -> bool {
+        let Some(key) = self.keys.remove(&task) else {
+            return false;
+        };
+
+        self.entries.remove(&key);
+        true
+    }
+
+    /// The wakeup time of the first entry
+    fn next_wakeup_time(&self) -> Option<usize> {
+        self.entries.first_key_value().map(|(&(wakeup_time, _), _)| wakeup_time)
+    }
+
+    /// Remove the first entry if it expired by `now`
+    fn pop_expired(&mut self, now: usize) -> Option<(T, Timeout)> {
+        let (task, timeout) = self.entries.first_entry().filter(|entry| now >= entry.key().0)?.remove();
+        self.keys.remove(&task);
+        Some((task, timeout))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SchedulerPipeline {
+    /// The ready tasks of every level
+    levels: [VecDeque<TaskBlock>; LEVELS],
+    sleep_queue: SleepQueue<TaskBlock>,
+
+    timer: Timer,
+    /// The timer count at the beginning of the previous request
+    last_request: usize,
+    /// The milliseconds elapsed since the previous request, the interrupted task ran for them
+    ran_for: usize,
+    /// The timer count the time slice of the running task ends at, [`None`] when the core is idle
+    slice_deadline: Option<usize>,
+    /// The timer count the ready tasks are next boosted at
+    next_boost: usize,
 }
 
 const fn timer_ms() -> usize {
@@ -40,6 +281,13 @@ const fn timer_ms() -> usize {
 
 impl SchedulerPipeline {
     pub(super) fn new(events: &mut Event) -> Self {
+        events.begin(|c, cx, _| {
+            let elapsed = c.scheduler.account_request();
+            if let (Some(thread), Some(process)) = (cx.interrupted_thread, cx.interrupted_process) {
+                c.process.charge_cpu_time(process, thread, elapsed as u64 * NANOS_PER_MILLI);
+            }
+        });
+
         events.hw_interrupts(|c, index| {
             if let InterruptIndex::TimerVector = index {
                 c.scheduler.handle_timer_interrupt();
@@ -47,44 +295,169 @@ impl SchedulerPipeline {
         });
 
         events.finalize(|c, cx| {
-            c.scheduler.finalize(cx);
+            c.scheduler.finalize(&c.thread, cx);
+        });
+
+        events.ipp_handler(|c, cx| {
+            WakeTaskPacket::handle(|WakeTaskPacket { task, value }| {
+                // The process might have been killed while the thread was blocked
+                if !task.valid() {
+                    return;
+                }
+
+                if let Some(value) = value {
+                    c.thread.set_return_value(task.thread, value);
+                }
+                // A futex wait timeout must not fire once the task is woken up
+                c.scheduler.cancel_sleep(task);
+                c.thread.scheduling_mut(task.thread).boost();
+                cx.added_tasks.push(task);
+            });
         });
 
         Self::default()
     }
 
-    fn finalize(&mut self, context: &mut PipelineContext) {
-        self.units.extend(&context.added_tasks);
+    fn finalize(&mut self, thread: &ThreadPipeline, context: &mut PipelineContext) {
+        for task in context.added_tasks.drain(..) {
+            self.push_back(thread, task);
+        }
+
+        // The tasks woken up in this request run once the running task used its slice, or right away
+        // on the next request when the core is idle
+        if self.slice_deadline.is_none() && self.levels.iter().any(|level| !level.is_empty()) {
+            let slice = context.scheduled_task.map_or(0, |task| thread.scheduling(task.thread).remaining_ms());
+            self.slice_deadline = Some(self.timer.count + slice);
+        }
 
-        context.should_hlt = (context.should_schedule || context.interrupted_slept || context.interrupted_freed)
+        context.should_hlt = (context.should_schedule
+            || context.interrupted_slept
+            || context.interrupted_blocked
+            || context.interrupted_freed)
             && context.scheduled_task.is_none();
+
+        // The timer keeps ticking from its interrupt until the scheduling starts
+        if context.should_schedule {
+            let wakeup_time = self.sleep_queue.next_wakeup_time();
+            let deadline = self.slice_deadline.into_iter().chain(wakeup_time).min();
+            if deadline != self.timer.deadline {
+                self.timer.arm(deadline);
+            }
+        }
     }
 
     pub fn timer_count(&self) -> usize {
-        self.timer_count
+        self.timer.count
+    }
+
+    /// Update the timer count at the beginning of a request, returns the milliseconds elapsed since
+    /// the previous request
+    fn account_request(&mut self) -> usize {
+        self.timer.update();
+        self.ran_for = self.timer.count - self.last_request;
+        self.last_request = self.timer.count;
+        self.ran_for
     }
 
     fn handle_timer_interrupt(&mut self) {
-        self.timer_count += timer_ms();
-        LAPIC.inner_mut().reset_timer_ms(timer_ms());
+        self.timer.update();
+
+        // Keep the timer firing until the next request arms it for the deadlines of the core, unless
+        // it fired before the deadline it was armed for
+        let deadline = match self.timer.deadline {
+            Some(deadline) if deadline > self.timer.count => deadline,
+            _ => self.timer.count + timer_ms(),
+        };
+        self.timer.arm(Some(deadline));
     }
 
     pub fn sleep_interrupted(&mut self, context: &mut PipelineContext, amount_millis: usize) {
         assert!(context.interrupted_task.is_some(), "sleep interrupted called with no interrupted task");
-        let sleep_entry =
-            SleepEntry { wakeup_time: self.timer_count + amount_millis, task: context.interrupted_task.unwrap() };
-
-        self.sleep_queue.push(Reverse(sleep_entry));
+        self.add_sleep(context.interrupted_task.unwrap(), amount_millis, Timeout::Sleep);
         context.interrupted_slept = true;
     }
 
+    /// Queue a sleep entry expiring in `millis` milliseconds for `task`, replacing its previous entry.
+    /// The task must be kept out of the run queue until then (e.g. it's blocked)
+    pub fn add_sleep(&mut self, task: TaskBlock, millis: usize, timeout: Timeout) {
+        self.sleep_queue.insert(task, self.timer.after(millis), timeout);
+    }
+
+    /// Cancel the sleep entry of `task` once it's woken up by something else, returns false if it
+    /// didn't have one
+    pub fn cancel_sleep(&mut self, task: TaskBlock) -> bool {
+        self.sleep_queue.cancel(task)
+    }
+
+    /// Move every task whose sleep entry expired to the front of its level, the earliest first
+    fn wake_sleepers(&mut self, thread: &mut ThreadPipeline, process: &mut ProcessPipeline, now: usize) {
+        let mut woken = Vec::new();
+        while let Some((task, timeout)) = self.sleep_queue.pop_expired(now) {
+            if !task.valid() {
+                continue;
+            }
+
+            if let Timeout::Futex { address } = timeout {
+                // Dequeued by a wake on another core in the meantime, the wake resumes it
+                if !process.futex_cancel(task, address) {
+                    continue;
+                }
+                thread.set_return_value(task.thread, SyscallError::TimedOut.encode());
+            }
+
+            thread.scheduling_mut(task.thread).boost();
+            woken.push(task);
+        }
+
+        for task in woken.into_iter().rev() {
+            self.push_front(thread, task);
+        }
+    }
+
+    /// Stop scheduling the interrupted task until it's passed to [`wake`]
+    pub fn block_interrupted(&mut self, context: &mut PipelineContext) {
+        assert!(context.interrupted_task.is_some(), "block interrupted called with no interrupted task");
+        context.interrupted_blocked = true;
+    }
+
+    /// Queue the interrupted task behind every ready task, including the ones woken during this
+    /// request
+    pub fn yield_interrupted(&mut self, context: &mut PipelineContext) {
+        assert!(context.interrupted_task.is_some(), "yield interrupted called with no interrupted task");
+        context.interrupted_yielded = true;
+    }
+
     pub(super) fn add_task(&mut self, init: TaskBlock) {
-        self.units.push_back(init);
+        self.levels[Scheduling::default().level()].push_back(init);
+    }
+
+    /// The level `task` is queued at, the invalid tasks are dropped once they're popped
+    fn level_of(thread: &ThreadPipeline, task: TaskBlock) -> usize {
+        if task.valid() { thread.scheduling(task.thread).level() } else { LEVELS - 1 }
+    }
+
+    fn push_back(&mut self, thread: &ThreadPipeline, task: TaskBlock) {
+        self.levels[Self::level_of(thread, task)].push_back(task);
+    }
+
+    fn push_front(&mut self, thread: &ThreadPipeline, task: TaskBlock) {
+        self.levels[Self::level_of(thread, task)].push_front(task);
+    }
+
+    /// Move every ready task to the top level, keeping their order
+    fn boost(&mut self, thread: &mut ThreadPipeline) {
+        let tasks: Vec<TaskBlock> = self.levels.iter_mut().flat_map(|level| level.drain(..)).collect();
+        for task in tasks {
+            if task.valid() {
+                thread.scheduling_mut(task.thread).lift();
+            }
+            self.push_back(thread, task);
+        }
     }
 
     fn migrate(&mut self, thread: &mut ThreadPipeline) {
         let local_core = CORE_ID.id();
-        let local_count = self.units.len();
+        let local_count = self.levels.iter().map(VecDeque::len).sum::<usize>();
 
         TASK_COUNT_EACH_CORE[local_core].store(local_count, Ordering::Relaxed);
 
@@ -108,7 +481,8 @@ impl SchedulerPipeline {
             return;
         }
 
-        while let Some(task) = self.units.pop_back() {
+        // The tasks of the lowest levels are the cpu bound ones
+        while let Some(task) = self.levels.iter_mut().rev().find_map(VecDeque::pop_back) {
             if !task.valid() {
                 continue;
             }
@@ -124,21 +498,50 @@ impl SchedulerPipeline {
         }
     }
 
-    pub fn schedule(&mut self, thread: &mut ThreadPipeline, context: &mut PipelineContext) {
-        if let Some(interrupted_task) = context.interrupted_task
-            && !(context.interrupted_slept || context.interrupted_freed)
+    pub fn schedule(
+        &mut self,
+        thread: &mut ThreadPipeline,
+        process: &mut ProcessPipeline,
+        context: &mut PipelineContext,
+    ) {
+        // The tasks woken during this request are only queued in finalize otherwise
+        if context.interrupted_yielded {
+            for task in context.added_tasks.drain(..) {
+                self.push_back(thread, task);
+            }
+        }
+
+        let now = self.timer.count;
+        let boost = now >= self.next_boost;
+        if boost {
+            self.next_boost = now + BOOST_INTERVAL_MS;
+            self.boost(thread);
+        }
+
+        let mut requeued = false;
+        if let Some(interrupted_task) = context.interrupted_task.filter(TaskBlock::valid)
+            && !(context.interrupted_slept || context.interrupted_blocked || context.interrupted_freed)
         {
-            self.units.push_back(interrupted_task);
+            let scheduling = thread.scheduling_mut(interrupted_task.thread);
+            if boost {
+                scheduling.lift();
+            }
+
+            // The task keeps running until its time slice runs out, unless a higher level task is ready
+            let expired = scheduling.charge(self.ran_for);
+            requeued = expired || context.interrupted_yielded;
+            if requeued {
+                self.push_back(thread, interrupted_task);
+            } else {
+                self.push_front(thread, interrupted_task);
+            }
         }
 
         self.migrate(thread);
 
-        if self.sleep_queue.peek().is_some_and(|Reverse(entry)| self.timer_count >= entry.wakeup_time) {
-            let task = self.sleep_queue.pop().unwrap().0.task;
-            self.units.push_front(task);
-        }
+        self.wake_sleepers(thread, process, now);
 
-        while let Some(task) = self.units.pop_front() {
+        while let Some(task) = self.levels.iter_mut().find_map(VecDeque::pop_front) {
             if task.valid() {
                 context.scheduled_task = Some(task);
                 break;
@@ -146,7 +549,128 @@ impl SchedulerPipeline {
                 log!(Debug, "invalid task! {task:?}");
             }
         }
+
+        // The slice of a task that keeps running goes on, a task running alone is interrupted at the end
+        // of its slice too, so it stops running once its process exits or it's killed from another core
+        let switched = requeued || context.scheduled_task != context.interrupted_task;
+        self.slice_deadline = match (context.scheduled_task, self.slice_deadline) {
+            (Some(_), Some(deadline)) if !switched => Some(deadline),
+            (Some(task), _) => Some(now + thread.scheduling(task.thread).remaining_ms()),
+            _ => None,
+        };
     }
 }
 
+/// Resume a task blocked with [`SchedulerPipeline::block_interrupted`], `value` is returned to the task
+/// in `rax`. Can be called from any core, the task is woken up on the core owning it.
+pub fn wake(task: TaskBlock, value: u64) {
+    WakeTaskPacket { task, value: Some(value) }.send(task.thread.core(), false);
+}
+
+/// Same as [`wake`] but keeps the saved state of the task as is, used for tasks that restart their
+/// syscall once resumed
+pub fn resume(task: TaskBlock) {
+    WakeTaskPacket { task, value: None }.send(task.thread.core(), false);
+}
+
+#[derive(Debug, IPPacket)]
+struct WakeTaskPacket {
+    task: TaskBlock,
+    /// Returned in `rax`, [`None`] to leave it as is
+    value: Option<u64>,
+}
+
 static TASK_COUNT_EACH_CORE: [AtomicUsize; MAX_CPU] = [const { AtomicUsize::new(usize::MAX) }; MAX_CPU];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_nice(nice: i8) -> Scheduling {
+        let mut scheduling = Scheduling::default();
+        scheduling.set_nice(nice).unwrap();
+        scheduling
+    }
+
+    #[test_case]
+    fn scheduling_base_level() {
+        assert_eq!(with_nice(MIN_NICE).base_level(), 0);
+        assert_eq!(with_nice(MAX_NICE).base_level(), LEVELS - 1);
+        assert_eq!(Scheduling::default().level(), with_nice(DEFAULT_NICE).base_level());
+        assert!(with_nice(MIN_NICE).level() < Scheduling::default().level());
+
+        assert_eq!(Scheduling::default().set_nice(MIN_NICE - 1), Err(SyscallError::InvalidArgument));
+        assert_eq!(Scheduling::default().set_nice(MAX_NICE + 1), Err(SyscallError::InvalidArgument));
+    }
+
+    #[test_case]
+    fn scheduling_charge() {
+        let mut scheduling = with_nice(MIN_NICE);
+        assert!(!scheduling.charge(scheduling.time_slice_ms() - 1));
+        assert!(scheduling.charge(1));
+        assert_eq!((scheduling.level(), scheduling.remaining_ms()), (1, 2 * timer_ms()));
+
+        // The lowest level is never left by charging
+        for _ in 0..LEVELS {
+            scheduling.charge(scheduling.remaining_ms());
+        }
+        assert_eq!(scheduling.level(), LEVELS - 1);
+
+        // Changing the nice value keeps the levels sunk, only the periodic boost lifts the thread
+        scheduling.set_nice(DEFAULT_NICE).unwrap();
+        assert_eq!(scheduling.level(), LEVELS - 1);
+        scheduling.boost();
+        assert_eq!(scheduling.level(), scheduling.base_level());
+        scheduling.lift();
+        assert_eq!((scheduling.level(), scheduling.remaining_ms()), (0, timer_ms()));
+    }
+
+    #[test_case]
+    fn timer_after() {
+        let timer = Timer { count: 10, remainder: 1, ..Timer::default() };
+        assert_eq!(timer.after(5), 16);
+        assert_eq!(timer.after(usize::MAX), usize::MAX);
+    }
+
+    fn pop_task(queue: &mut SleepQueue<u32>, now: usize) -> Option<u32> {
+        queue.pop_expired(now).map(|(task, _)| task)
+    }
+
+    #[test_case]
+    fn sleep_queue_saturated_wakeup() {
+        let timer = Timer { count: 10, remainder: 1, ..Timer::default() };
+        let mut queue = SleepQueue::default();
+        queue.insert(1, timer.after(usize::MAX), Timeout::Sleep);
+        queue.insert(2, timer.after(5), Timeout::Sleep);
+
+        // The saturated entry expires last, and only once the count reaches the end
+        assert_eq!(queue.next_wakeup_time(), Some(16));
+        assert_eq!(pop_task(&mut queue, usize::MAX - 1), Some(2));
+        assert_eq!(pop_task(&mut queue, usize::MAX - 1), None);
+        assert_eq!(queue.next_wakeup_time(), Some(usize::MAX));
+        assert_eq!(pop_task(&mut queue, usize::MAX), Some(1));
+        assert_eq!(queue.next_wakeup_time(), None);
+    }
+
+    #[test_case]
+    fn sleep_queue_cancel() {
+        let mut queue = SleepQueue::default();
+        queue.insert(1, 10, Timeout::Sleep);
+        queue.insert(2, 10, Timeout::Sleep);
+        queue.insert(3, 20, Timeout::Sleep);
+
+        assert!(queue.cancel(2));
+        assert!(!queue.cancel(2));
+        assert_eq!((queue.entries.len(), queue.keys.len()), (2, 2));
+
+        // The entries around the cancelled one keep their order
+        assert_eq!(pop_task(&mut queue, 20), Some(1));
+        assert_eq!(pop_task(&mut queue, 20), Some(3));
+        assert_eq!(pop_task(&mut queue, 20), None);
+
+        // A new entry of a task replaces its previous one
+        queue.insert(1, 30, Timeout::Sleep);
+        queue.insert(1, 5, Timeout::Sleep);
+        assert_eq!((queue.entries.len(), queue.next_wakeup_time()), (1, Some(5)));
+    }
+}
@@ -9,7 +9,7 @@
 //! to the thread resources, This is done because directly giving a reference to the thread resources
 //! can causes borrow checker problems.
 
-use core::{assert_matches, mem::zeroed, num::NonZeroUsize};
+use core::{assert_matches, num::NonZeroUsize};
 
 use alloc::vec::Vec;
 use kernel_proc::IPPacket;
@@ -23,18 +23,24 @@ use crate::{
         pipeline::{
             CURRENT_THREAD_ID, CommonRequestContext, Event, PipelineContext, TaskBlock, TaskProcesserState,
             process::{Process, ProcessPipeline},
+            scheduler::Scheduling,
         },
         syscall::MIGRATE_RECEIVED_COUNT,
     },
 };
 
+pub mod extended_state;
 mod id;
 
+const SYSCALL_INSTRUCTION_SIZE: usize = 2;
+
 #[derive(Debug, Default)]
 pub struct ThreadPipeline {
     pool: Vec<ThreadContext>,
     unused_thread: Vec<usize>,
     migrated_thread: Vec<usize>,
+    /// The thread whose extended state is in the registers of the core, See [`extended_state`]
+    extended_state_owner: Option<usize>,
 }
 
 impl ThreadPipeline {
@@ -53,7 +59,11 @@ impl ThreadPipeline {
     fn begin(&mut self, context: &CommonRequestContext<'_>) -> Option<Thread> {
         let thread = Thread::capture()?;
         assert_eq!(self.thread_context(thread).state, ThreadState::Active, "Captured thread isn't active");
-        self.thread_context_mut(thread).processor_state = TaskProcesserState::from(context);
+        let state = &mut self.thread_context_mut(thread).processor_state;
+        *state = TaskProcesserState {
+            extended_state: core::mem::take(&mut state.extended_state),
+            ..TaskProcesserState::from(context)
+        };
         Some(thread)
     }
 
@@ -63,16 +73,91 @@ impl ThreadPipeline {
         }
 
         if let Some(task) = ctx.scheduled_task {
+            self.switch_extended_state(task.thread);
             *CURRENT_THREAD_ID.borrow_mut() = task.thread.global_id.get();
         } else {
             *CURRENT_THREAD_ID.borrow_mut() = 0;
         }
     }
 
+    /// Load the extended state of `thread` in the registers, saving the state of the thread that was
+    /// using them. Nothing is done while the same thread keeps running on the core
+    fn switch_extended_state(&mut self, thread: Thread) {
+        let id = thread.local_id().thread;
+        if self.extended_state_owner == Some(id) {
+            return;
+        }
+
+        if let Some(owner) = self.extended_state_owner.replace(id) {
+            self.pool[owner].processor_state.extended_state.save();
+        }
+        self.pool[id].processor_state.extended_state.restore();
+    }
+
+    /// The extended state of the thread, see [`ExtendedState::to_bytes`]. The registers are saved
+    /// first when they hold the state of the thread
+    ///
+    /// [`ExtendedState::to_bytes`]: extended_state::ExtendedState::to_bytes
+    pub fn extended_state_bytes(&mut self, thread: Thread) -> Vec<u8> {
+        let id = thread.local_id().thread;
+        if self.extended_state_owner == Some(id) {
+            self.pool[id].processor_state.extended_state.save();
+        }
+        self.pool[id].processor_state.extended_state.to_bytes()
+    }
+
+    /// Replace the extended state of the thread, see [`ExtendedState::set_bytes`]. The registers are
+    /// loaded right away when they hold the state of the thread
+    ///
+    /// [`ExtendedState::set_bytes`]: extended_state::ExtendedState::set_bytes
+    pub fn set_extended_state_bytes(&mut self, thread: Thread, bytes: &[u8]) {
+        let id = thread.local_id().thread;
+        let extended_state = &mut self.pool[id].processor_state.extended_state;
+        extended_state.set_bytes(bytes);
+        if self.extended_state_owner == Some(id) {
+            extended_state.restore();
+        }
+    }
+
     pub fn task_processor_state(&self, thread: Thread) -> &TaskProcesserState {
         &self.thread_context(thread).processor_state
     }
 
+    pub fn task_processor_state_mut(&mut self, thread: Thread) -> &mut TaskProcesserState {
+        &mut self.thread_context_mut(thread).processor_state
+    }
+
+    /// Set the value of `rax` the thread will see when it's resumed
+    pub fn set_return_value(&mut self, thread: Thread, value: u64) {
+        self.thread_context_mut(thread).processor_state.rax = value;
+    }
+
+    /// Make the thread execute the syscall `id` again once it's resumed, the `syscall` instruction
+    /// is 2 bytes long and every argument register is preserved
+    pub fn restart_syscall(&mut self, thread: Thread, id: u64) {
+        let state = &mut self.thread_context_mut(thread).processor_state;
+        state.instruction_pointer = state.instruction_pointer - SYSCALL_INSTRUCTION_SIZE;
+        state.rax = id;
+    }
+
+    pub fn scheduling(&self, thread: Thread) -> &Scheduling {
+        &self.thread_context(thread).scheduling
+    }
+
+    pub fn scheduling_mut(&mut self, thread: Thread) -> &mut Scheduling {
+        &mut self.thread_context_mut(thread).scheduling
+    }
+
+    pub fn stack_top(&self, thread: Thread) -> VirtAddr {
+        self.thread_context(thread).stack().top()
+    }
+
+    /// Set the stack pointer the thread starts with, used to place data (e.g. the program arguments)
+    /// at the top of its stack
+    pub fn set_stack_pointer(&mut self, thread: Thread, stack_pointer: VirtAddr) {
+        self.thread_context_mut(thread).processor_state.stack_pointer = stack_pointer;
+    }
+
     fn handle_ipp(&mut self, pipeline_context: &mut PipelineContext) {
         ThreadMigratePacket::handle(|ThreadMigratePacket { context, process, global_id }| {
             assert_matches!(context.state, ThreadState::Active, "Dead thread were migrated");
@@ -98,14 +183,20 @@ impl ThreadPipeline {
 
     pub fn migrate(&mut self, destination: CoreId, TaskBlock { thread, process }: TaskBlock) {
         let id = thread.local_id().thread;
+        if self.extended_state_owner == Some(id) {
+            self.extended_state_owner = None;
+            self.pool[id].processor_state.extended_state.save();
+        }
 
+        let parent_process = self.thread_context(thread).parent_process;
         let context = core::mem::replace(
             self.thread_context_mut(thread),
             ThreadContext {
                 state: ThreadState::Migrated,
-                // SAFETY: This is fine since the new ThreadState is set to migrated which will be
-                // replaced with the new context when this is reused
-                ..unsafe { zeroed() }
+                processor_state: TaskProcesserState::default(),
+                parent_process,
+                stack: None,
+                scheduling: Scheduling::default(),
             },
         );
         id::invalidate(thread);
@@ -119,6 +210,9 @@ impl ThreadPipeline {
         let id = thread.local_id().thread;
         id::free_thread(thread);
         self.pool[id].state = ThreadState::Inactive;
+        if self.extended_state_owner == Some(id) {
+            self.extended_state_owner = None;
+        }
         self.unused_thread.push(id);
     }
 
@@ -134,17 +228,26 @@ impl ThreadPipeline {
             );
 
             match (thread_ctx.state, thread_ctx.parent_process == parent_process) {
-                (ThreadState::Migrated, ..) | (ThreadState::Inactive, false) => {
-                    // FIXME: This leaks the stack of the previous parent process
+                (ThreadState::Migrated, ..) => {
                     *thread_ctx = ThreadContext::new(process.alloc_stack(parent_process), parent_process, start);
                 }
+                (ThreadState::Inactive, false) => {
+                    let stack = process.alloc_stack(parent_process);
+                    let previous = core::mem::replace(thread_ctx, ThreadContext::new(stack, parent_process, start));
+                    if let Some(stack) = previous.stack {
+                        process.dealloc_stack(previous.parent_process, stack);
+                    }
+                }
                 (ThreadState::Inactive, true) => {
-                    // TODO: Zero out the stack if possible
+                    let stack = thread_ctx.stack();
+                    process.zero_stack(parent_process, stack);
+                    let stack_top = stack.top();
                     thread_ctx.processor_state = TaskProcesserState {
                         instruction_pointer: start,
-                        stack_pointer: thread_ctx.stack.top() - 8usize,
+                        stack_pointer: stack_top - 8usize,
                         ..Default::default()
                     };
+                    thread_ctx.scheduling = Scheduling::default();
                 }
                 (ThreadState::Active, ..) => {
                     panic!("There shouldn't be an alive thread in the unused thread pool")
@@ -191,7 +294,9 @@ struct ThreadContext {
     state: ThreadState,
     processor_state: TaskProcesserState,
     parent_process: Process,
-    stack: Stack,
+    /// [`None`] for the placeholder left behind by a migrated thread, the stack moved along it
+    stack: Option<Stack>,
+    scheduling: Scheduling,
 }
 
 impl ThreadContext {
@@ -204,9 +309,14 @@ impl ThreadContext {
                 ..Default::default()
             },
             parent_process: parent,
-            stack,
+            stack: Some(stack),
+            scheduling: Scheduling::default(),
         }
     }
+
+    fn stack(&self) -> &Stack {
+        self.stack.as_ref().expect("A migrated thread doesn't have a stack")
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -239,6 +349,11 @@ impl Thread {
         self.global_id
     }
 
+    /// The core the thread is running on
+    pub fn core(&self) -> CoreId {
+        self.local_id().core
+    }
+
     fn capture() -> Option<Self> {
         if *CURRENT_THREAD_ID.borrow() == 0 {
             return None;
@@ -0,0 +1,206 @@
+//! The extended state of a thread, the x87, SSE and AVX registers, saved with XSAVE (or FXSAVE when
+//! the OS didn't enable XSAVE) in an area sized from CPUID for the components enabled in XCR0.
+//!
+//! The kernel never touches these registers (it's built with soft-float), so the state of a thread
+//! is left in the registers until another thread runs on the core (See [`ThreadPipeline`]). The
+//! save area of a thread is only allocated the first time its state is saved, until then the thread
+//! starts from the initial state.
+//!
+//! [`ThreadPipeline`]: super::ThreadPipeline
+
+use core::{arch::asm, fmt};
+
+use alloc::{vec, vec::Vec};
+use pager::registers::Xcr0;
+use raw_cpuid::CpuId;
+use spin::Once;
+
+/// The x87 and SSE state, the only part written by FXSAVE
+const LEGACY_AREA_SIZE: usize = 512;
+/// The XSAVE header, following the legacy area
+const HEADER_SIZE: usize = 64;
+const AREA_ALIGNMENT: usize = 64;
+
+/// The x87 control word after `fninit`, every exception masked
+const DEFAULT_FCW: u16 = 0x037F;
+/// The MXCSR after reset, every exception masked
+const DEFAULT_MXCSR: u32 = 0x1F80;
+const MXCSR_OFFSET: usize = 24;
+/// The MXCSR bits every x86_64 core supports, setting any other bit makes the restore fault
+const MXCSR_MASK: u32 = 0xFFBF;
+/// The components of the XSAVE area holding a saved state, part of the header
+const XSTATE_BV_OFFSET: usize = LEGACY_AREA_SIZE;
+
+static SAVE_INSTRUCTION: Once<SaveInstruction> = Once::new();
+static INITIAL_AREA: InitialArea = InitialArea::new();
+
+#[derive(Debug, Clone, Copy)]
+enum SaveInstruction {
+    XSave { area_size: usize },
+    FxSave,
+}
+
+impl SaveInstruction {
+    fn get() -> Self {
+        *SAVE_INSTRUCTION.call_once(|| {
+            let cpuid = CpuId::new();
+            let enabled = cpuid.get_feature_info().is_some_and(|info| info.has_oxsave());
+            match cpuid.get_extended_state_info().filter(|_| enabled) {
+                Some(info) => Self::XSave { area_size: info.xsave_area_size_enabled_features() as usize },
+                None => Self::FxSave,
+            }
+        })
+    }
+
+    fn area_size(self) -> usize {
+        match self {
+            Self::XSave { area_size } => area_size,
+            Self::FxSave => LEGACY_AREA_SIZE,
+        }
+    }
+
+    /// # Safety
+    /// `area` must be 64 bytes aligned and valid for writes of [`Self::area_size`] bytes, its XSAVE
+    /// header must only have been written by a previous save
+    unsafe fn save(self, area: *mut u8) {
+        // SAFETY: Every component enabled in XCR0 is saved, which fits in the area as uphold by the caller
+        unsafe {
+            match self {
+                Self::XSave { .. } => asm!(
+                    "xsave64 [{}]",
+                    in(reg) area,
+                    in("eax") u32::MAX,
+                    in("edx") u32::MAX,
+                    options(nostack, preserves_flags)
+                ),
+                Self::FxSave => asm!("fxsave64 [{}]", in(reg) area, options(nostack, preserves_flags)),
+            }
+        }
+    }
+
+    /// # Safety
+    /// `area` must be 64 bytes aligned, and hold a state written by [`Self::save`] or the initial state
+    unsafe fn restore(self, area: *const u8) {
+        // SAFETY: The area holds a valid state as uphold by the caller, the components set to their
+        // initial state in the XSAVE header aren't read
+        unsafe {
+            match self {
+                Self::XSave { .. } => asm!(
+                    "xrstor64 [{}]",
+                    in(reg) area,
+                    in("eax") u32::MAX,
+                    in("edx") u32::MAX,
+                    options(readonly, nostack, preserves_flags)
+                ),
+                Self::FxSave => asm!("fxrstor64 [{}]", in(reg) area, options(readonly, nostack, preserves_flags)),
+            }
+        }
+    }
+}
+
+/// The state every thread starts with, the XSAVE header marks every component as being in its
+/// initial state, but FXRSTOR (and XRSTOR for MXCSR) still read the control registers from the
+/// legacy area
+#[repr(C, align(64))]
+struct InitialArea([u8; LEGACY_AREA_SIZE + HEADER_SIZE]);
+
+impl InitialArea {
+    const fn new() -> Self {
+        let mut area = [0; LEGACY_AREA_SIZE + HEADER_SIZE];
+        let fcw = DEFAULT_FCW.to_le_bytes();
+        let mxcsr = DEFAULT_MXCSR.to_le_bytes();
+        area[0] = fcw[0];
+        area[1] = fcw[1];
+        area[MXCSR_OFFSET] = mxcsr[0];
+        area[MXCSR_OFFSET + 1] = mxcsr[1];
+        area[MXCSR_OFFSET + 2] = mxcsr[2];
+        area[MXCSR_OFFSET + 3] = mxcsr[3];
+        Self(area)
+    }
+}
+
+/// Gives the save area its alignment
+#[repr(C, align(64))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct AreaChunk([u8; AREA_ALIGNMENT]);
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ExtendedState {
+    /// Empty until the state is saved for the first time
+    area: Vec<AreaChunk>,
+}
+
+impl ExtendedState {
+    /// Save the extended registers of the core, they must hold the state of this thread
+    pub fn save(&mut self) {
+        let instruction = SaveInstruction::get();
+        if self.area.is_empty() {
+            self.area = vec![AreaChunk([0; AREA_ALIGNMENT]); instruction.area_size().div_ceil(AREA_ALIGNMENT)];
+        }
+
+        // SAFETY: The chunks are 64 bytes aligned, cover the area size, and start zeroed
+        unsafe { instruction.save(self.area.as_mut_ptr().cast()) }
+    }
+
+    /// Load the saved state in the extended registers of the core, or the initial state if it was
+    /// never saved
+    pub fn restore(&self) {
+        let area = if self.area.is_empty() { INITIAL_AREA.0.as_ptr() } else { self.area.as_ptr().cast() };
+
+        // SAFETY: Both areas are 64 bytes aligned, and hold either a saved state or the initial state
+        unsafe { SaveInstruction::get().restore(area) }
+    }
+
+    /// The size of the state returned by [`Self::to_bytes`]
+    pub fn size() -> usize {
+        SaveInstruction::get().area_size()
+    }
+
+    /// The saved state in the layout of the save instruction, or the initial state if it was never saved
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::size()];
+        if self.area.is_empty() {
+            let length = bytes.len().min(INITIAL_AREA.0.len());
+            bytes[..length].copy_from_slice(&INITIAL_AREA.0[..length]);
+        } else {
+            for (bytes, chunk) in bytes.chunks_mut(AREA_ALIGNMENT).zip(&self.area) {
+                bytes.copy_from_slice(&chunk.0[..bytes.len()]);
+            }
+        }
+        bytes
+    }
+
+    /// Replace the saved state with `bytes` returned by [`Self::to_bytes`], the bytes come from the
+    /// user so the fields the restore would fault on are sanitized
+    pub fn set_bytes(&mut self, bytes: &[u8]) {
+        let instruction = SaveInstruction::get();
+        assert_eq!(bytes.len(), instruction.area_size(), "Extended state of the wrong size");
+
+        let mut bytes = bytes.to_vec();
+        let mxcsr = u32::from_le_bytes(bytes[MXCSR_OFFSET..MXCSR_OFFSET + 4].try_into().unwrap()) & MXCSR_MASK;
+        bytes[MXCSR_OFFSET..MXCSR_OFFSET + 4].copy_from_slice(&mxcsr.to_le_bytes());
+
+        if let SaveInstruction::XSave { .. } = instruction {
+            let header = &mut bytes[XSTATE_BV_OFFSET..XSTATE_BV_OFFSET + HEADER_SIZE];
+            let components = u64::from_le_bytes(header[..8].try_into().unwrap()) & Xcr0::read().bits();
+            // The compaction bits and the reserved part of the header must be zero
+            header.fill(0);
+            header[..8].copy_from_slice(&components.to_le_bytes());
+        }
+
+        self.area = bytes
+            .chunks(AREA_ALIGNMENT)
+            .map(|bytes| {
+                let mut chunk = AreaChunk([0; AREA_ALIGNMENT]);
+                chunk.0[..bytes.len()].copy_from_slice(bytes);
+                chunk
+            })
+            .collect();
+    }
+}
+
+impl fmt::Debug for ExtendedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedState").field("saved", &!self.area.is_empty()).finish()
+    }
+}
@@ -1,51 +1,43 @@
 use core::sync::atomic::AtomicUsize;
 
+use alloc::{string::String, vec, vec::Vec};
+use hotline::{ABI_VERSION, ANY_CHILD, MESSAGE_SIZE, NO_CAPABILITY, Syscall, SyscallError, encode_result};
 use pager::address::VirtAddr;
 
 use crate::{
+    TESTING,
     logger::LOGGER,
-    userland::pipeline::{CommonRequestContext, ControlPipeline, PipelineContext},
+    userland::pipeline::{CommonRequestContext, ControlPipeline, PipelineContext, TaskBlock},
 };
 
-#[derive(Debug, Clone, Copy)]
-pub struct SyscallId(pub u32);
-
-enum Syscall {
-    Exit,
-    Sleep,
-    Spawn,
-    ExitThread,
-    Test,
-    Flush,
-}
+/// The maximum amount of bytes moved by a single read or write syscall, the rest is left for the caller
+/// to retry
+const MAX_IO_SIZE: usize = 0x10000;
 
-impl TryFrom<SyscallId> for Syscall {
-    type Error = u32;
+/// The longest program name accepted by the spawn process syscall
+const MAX_PROGRAM_NAME_SIZE: usize = 0x100;
 
-    fn try_from(value: SyscallId) -> Result<Self, Self::Error> {
-        match value {
-            SyscallId(0) => Ok(Self::Exit),
-            SyscallId(1) => Ok(Self::Sleep),
-            SyscallId(2) => Ok(Self::Spawn),
-            SyscallId(3) => Ok(Self::ExitThread),
-            SyscallId(4) => Ok(Self::Test),
-            SyscallId(5) => Ok(Self::Flush),
-            SyscallId(unknown) => Err(unknown),
-        }
-    }
-}
+/// The maximum size of the arguments buffer passed to the spawn process syscall
+const MAX_ARGUMENTS_SIZE: usize = 0x4000;
+
+/// The maximum number of arguments passed to the spawn process syscall, along with
+/// [`MAX_ARGUMENTS_SIZE`] it keeps the arguments well within the initial stack of the program
+const MAX_ARGUMENT_COUNT: usize = 0x400;
+
+/// The raw syscall number, as passed in `rax`
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallId(pub u64);
 
 pub static MIGRATE_COUNT: AtomicUsize = AtomicUsize::new(0);
 pub static MIGRATE_RECEIVED_COUNT: AtomicUsize = AtomicUsize::new(0);
 pub static THREAD_FREE_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 pub(super) fn syscall_handle(
-    rq_context: &CommonRequestContext,
+    rq_context: &mut CommonRequestContext,
     pipeline: &mut ControlPipeline,
     pipeline_context: &mut PipelineContext,
     syscall: SyscallId,
 ) {
-    let syscall = Syscall::try_from(syscall).unwrap_or(Syscall::Exit);
     let Some(calling_task) = pipeline_context.interrupted_task else {
         return;
     };
@@ -54,20 +46,34 @@ pub(super) fn syscall_handle(
         return;
     }
 
+    let result = Syscall::decode(syscall.0, rq_context.stack_frame.arguments())
+        .and_then(|syscall| execute(pipeline, pipeline_context, calling_task, syscall));
+
+    let raw = encode_result(result);
+    rq_context.stack_frame.rax = raw;
+    pipeline.syscall_return(pipeline_context, syscall, raw);
+}
+
+fn execute(
+    pipeline: &mut ControlPipeline,
+    pipeline_context: &mut PipelineContext,
+    calling_task: TaskBlock,
+    syscall: Syscall,
+) -> Result<u64, SyscallError> {
     match syscall {
-        Syscall::Exit => pipeline.free_process(calling_task.process),
-        Syscall::Sleep => pipeline.sleep_interrupted(pipeline_context, rq_context.stack_frame.rdx as usize),
-        Syscall::Spawn => {
-            let start = VirtAddr::new(rq_context.stack_frame.rdx);
-            if pipeline.alloc_thread(pipeline_context, calling_task.process, start).is_none() {
-                pipeline.free_process(calling_task.process);
-            }
+        Syscall::Exit { code } => pipeline.exit_process(calling_task.process, code),
+        Syscall::Sleep { millis } => pipeline.sleep_interrupted(pipeline_context, millis),
+        Syscall::Yield {} => pipeline.yield_interrupted(pipeline_context),
+        Syscall::Spawn { entry } => {
+            let start = user_address(entry)?;
+            let task = pipeline.alloc_thread(pipeline_context, calling_task.process, start)?;
+            return Ok(task.thread.id().get() as u64);
         }
-        Syscall::ExitThread => {
+        Syscall::ExitThread {} => {
             pipeline.free_thread(pipeline_context, calling_task.thread);
             THREAD_FREE_COUNT.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
         }
-        Syscall::Flush => {
+        Syscall::Flush {} => {
             log!(Debug, "Thread free total count: {}", THREAD_FREE_COUNT.load(core::sync::atomic::Ordering::Relaxed));
             log!(Debug, "migrate total count: {}", MIGRATE_COUNT.load(core::sync::atomic::Ordering::Relaxed));
             log!(
@@ -77,8 +83,168 @@ pub(super) fn syscall_handle(
             );
             LOGGER.flush_all(&[|s| serial_print!("{s}")]);
         }
-        Syscall::Test => {
-            serial_print!("{}", char::from_u32(rq_context.stack_frame.rdx.try_into().unwrap_or(0)).unwrap_or('?'));
+        Syscall::Test { c } => {
+            serial_print!("{c}");
+        }
+        Syscall::AbiVersion {} => return Ok(ABI_VERSION as u64),
+        Syscall::MemoryMap { address, length, protection } => {
+            let address = match address {
+                0 => None,
+                address => Some(user_address(address)?),
+            };
+            let start = pipeline.map_memory(calling_task.process, address, length as u64, protection)?;
+            return Ok(start.as_u64());
+        }
+        Syscall::MemoryUnmap { address, length } => {
+            pipeline.unmap_memory(calling_task.process, user_address(address)?, length as u64)?
+        }
+        Syscall::MemoryProtect { address, length, protection } => {
+            pipeline.protect_memory(calling_task.process, user_address(address)?, length as u64, protection)?
+        }
+        Syscall::Write { fd, buffer, length } => {
+            let mut bytes = vec![0; length.min(MAX_IO_SIZE)];
+            pipeline.copy_from_user(calling_task.process, &mut bytes, user_address(buffer)?)?;
+
+            // When the caller is blocked, the syscall is restarted once it's woken up
+            let written = pipeline.write_file(pipeline_context, fd, &bytes)?;
+            return Ok(written.unwrap_or_default() as u64);
+        }
+        Syscall::Read { fd, buffer, length } => {
+            // When the caller is blocked, the syscall is restarted once it's woken up
+            let read = pipeline.read_file(pipeline_context, fd, user_address(buffer)?, length.min(MAX_IO_SIZE))?;
+            return Ok(read.unwrap_or_default() as u64);
+        }
+        Syscall::Pipe { fds } => {
+            let fds = user_address(fds)?;
+            let (read, write) = pipeline.pipe(calling_task.process)?;
+
+            let mut bytes = [0; 2 * size_of::<usize>()];
+            bytes[..size_of::<usize>()].copy_from_slice(&read.to_ne_bytes());
+            bytes[size_of::<usize>()..].copy_from_slice(&write.to_ne_bytes());
+            if let Err(error) = pipeline.copy_to_user(calling_task.process, fds, &bytes) {
+                pipeline.close_file(calling_task.process, read)?;
+                pipeline.close_file(calling_task.process, write)?;
+                return Err(error);
+            }
+        }
+        Syscall::Close { fd } => pipeline.close_file(calling_task.process, fd)?,
+        Syscall::Dup { fd } => return Ok(pipeline.dup_file(calling_task.process, fd)? as u64),
+        Syscall::PortCreate {} => return Ok(pipeline.port_create(calling_task.process)? as u64),
+        Syscall::PortSend { port, message, grant, grant_length, capability } => {
+            let mut data = [0; MESSAGE_SIZE];
+            pipeline.copy_from_user(calling_task.process, &mut data, user_address(message)?)?;
+
+            let grant = match grant_length {
+                0 => None,
+                length => Some((user_address(grant)?, length as u64)),
+            };
+            let capability = (capability != NO_CAPABILITY).then_some(capability);
+            pipeline.port_send(calling_task.process, port, data, grant, capability)?
+        }
+        Syscall::PortReceive { port, message, info } => {
+            let (message, info_address) = (user_address(message)?, user_address(info)?);
+
+            // When the caller is blocked, the syscall is restarted once a message is sent
+            let Some((data, info)) = pipeline.port_receive(pipeline_context, port)? else {
+                return Ok(0);
+            };
+
+            pipeline.copy_to_user(calling_task.process, message, &data)?;
+            pipeline.copy_to_user(calling_task.process, info_address, &info.to_ne_bytes())?;
+        }
+        Syscall::SharedMemoryCreate { length } => {
+            return Ok(pipeline.shared_memory_create(calling_task.process, length as u64)? as u64);
+        }
+        Syscall::SharedMemoryMap { handle, address, protection } => {
+            let address = match address {
+                0 => None,
+                address => Some(user_address(address)?),
+            };
+            let start = pipeline.shared_memory_map(calling_task.process, handle, address, protection)?;
+            return Ok(start.as_u64());
+        }
+        Syscall::ClockGet { clock } => return Ok(pipeline.clock_time(clock)),
+        Syscall::ProcessInfo { pid, info } => {
+            let info_address = user_address(info)?;
+            let info = pipeline.process_info(pid)?;
+            pipeline.copy_to_user(calling_task.process, info_address, &info.to_ne_bytes())?;
+            return Ok(info.pid as u64);
+        }
+        Syscall::ThreadInfo { pid, thread_id, info } => {
+            let info_address = user_address(info)?;
+            let info = pipeline.thread_info(pid, thread_id)?;
+            pipeline.copy_to_user(calling_task.process, info_address, &info.to_ne_bytes())?;
+            return Ok(info.thread_id as u64);
+        }
+        Syscall::SignalAction { signal, handler, trampoline } => {
+            let previous = pipeline.signal_action(calling_task.process, signal, handler, trampoline)?;
+            return Ok(previous as u64);
+        }
+        Syscall::Kill { pid, signal } => pipeline.kill(pid, signal)?,
+        Syscall::ThreadKill { thread_id, signal } => pipeline.thread_kill(calling_task.process, thread_id, signal)?,
+        Syscall::SetPriority { nice } => pipeline.set_priority(calling_task.thread, nice)?,
+        Syscall::SignalReturn { frame } => pipeline.signal_return(pipeline_context, user_address(frame)?)?,
+        Syscall::CapabilityClose { handle } => pipeline.close_capability(calling_task.process, handle)?,
+        Syscall::Join { thread_id } => pipeline.join(pipeline_context, thread_id)?,
+        Syscall::SpawnProcess { name, name_length, args, args_length } => {
+            if name_length > MAX_PROGRAM_NAME_SIZE || args_length > MAX_ARGUMENTS_SIZE {
+                return Err(SyscallError::InvalidArgument);
+            }
+
+            let mut name_bytes = vec![0; name_length];
+            pipeline.copy_from_user(calling_task.process, &mut name_bytes, user_address(name)?)?;
+            let name = core::str::from_utf8(&name_bytes).map_err(|_| SyscallError::InvalidArgument)?;
+
+            let mut args_bytes = vec![0; args_length];
+            pipeline.copy_from_user(calling_task.process, &mut args_bytes, user_address(args)?)?;
+            let args: Vec<&[u8]> = match args_bytes.split_last() {
+                None => Vec::new(),
+                Some((0, args)) => args.split(|byte| *byte == 0).collect(),
+                Some(_) => return Err(SyscallError::InvalidArgument),
+            };
+            if args.len() > MAX_ARGUMENT_COUNT {
+                return Err(SyscallError::InvalidArgument);
+            }
+
+            let process = pipeline.spawn_process(pipeline_context, calling_task.process, name, &args)?;
+            return Ok(process.pid() as u64);
+        }
+        Syscall::FutexWait { address, expected } => {
+            pipeline.futex_wait(pipeline_context, user_address(address)?, expected, None)?
+        }
+        Syscall::FutexWaitTimeout { address, expected, millis } => {
+            pipeline.futex_wait(pipeline_context, user_address(address)?, expected, Some(millis))?
+        }
+        Syscall::FutexWake { address, count } => {
+            let woken = pipeline.futex_wake(pipeline_context, calling_task.process, user_address(address)?, count)?;
+            return Ok(woken as u64);
+        }
+        Syscall::Wait { pid, status } => {
+            let pid = (pid != ANY_CHILD).then_some(pid);
+            let status = match status {
+                0 => None,
+                status => Some(user_address(status)?),
+            };
+
+            // When the caller is blocked, the pid is returned once it's woken up
+            let collected = pipeline.wait(pipeline_context, pid, status)?;
+            return Ok(collected.unwrap_or_default() as u64);
         }
     }
+
+    Ok(0)
+}
+
+/// Write the bytes to the serial port and the screen (if available) at once, invalid utf-8 is
+/// replaced with `U+FFFD`
+pub(super) fn write_console(bytes: &[u8]) {
+    let text = String::from_utf8_lossy(bytes);
+    serial_print!("{text}");
+    if crate::print::DRIVER.get().is_some() && !TESTING {
+        print!("{text}");
+    }
+}
+
+fn user_address(address: usize) -> Result<VirtAddr, SyscallError> {
+    VirtAddr::new_checked(address as u64).map_err(|_| SyscallError::BadAddress)
 }
@@ -0,0 +1,130 @@
+//! Helpers to access the memory of the current user process from the kernel.
+//!
+//! **Use** [`copy_from_user`] **and** [`copy_to_user`] **whenever a syscall takes a pointer** (usually
+//! through the process pipeline, which makes the process page table active). They check the range
+//! against the lower half mapper, then copy with a routine that can recover from a page fault,
+//! returning [`SyscallError::BadAddress`] instead of hitting the panicking page fault handler.
+
+use core::arch::naked_asm;
+
+use hotline::SyscallError;
+use pager::{
+    EntryFlags,
+    address::{Page, Size4K, VirtAddr},
+    paging::{mapper::Mapper, table::RootRecurseLowerHalf},
+};
+
+use crate::interrupt::{ExtendedInterruptStackFrame, idt::PageFaultErrorCode};
+
+unsafe extern "C" {
+    /// The only instruction of [`user_copy`] touching the user memory
+    static user_copy_access: u8;
+    /// Where [`user_copy`] continues when the access faults
+    static user_copy_fixup: u8;
+}
+
+/// Check that the whole range is mapped in `mapper` as user accessible (and writable if `write` is
+/// set), returns the last address of the range
+pub fn validate_user_range(
+    mapper: &Mapper<RootRecurseLowerHalf>,
+    address: VirtAddr,
+    length: usize,
+    write: bool,
+) -> Result<VirtAddr, SyscallError> {
+    if length == 0 {
+        return Ok(address);
+    }
+
+    let end = address.as_u64().checked_add(length as u64 - 1).ok_or(SyscallError::BadAddress)?;
+    let end = VirtAddr::new_checked(end).map_err(|_| SyscallError::BadAddress)?;
+    if address.is_canonical_higher_half() || end.is_canonical_higher_half() {
+        return Err(SyscallError::BadAddress);
+    }
+
+    let required = if write { EntryFlags::USER_ACCESSIBLE | EntryFlags::WRITABLE } else { EntryFlags::USER_ACCESSIBLE };
+    let accessible =
+        Page::range_inclusive(Page::<Size4K>::containing_address(address), Page::<Size4K>::containing_address(end))
+            .all(|page| mapper.translate_page_flags(page).is_some_and(|flags| flags.contains(required)));
+
+    if !accessible {
+        return Err(SyscallError::BadAddress);
+    }
+
+    Ok(end)
+}
+
+/// Copy `destination.len()` bytes from the user memory at `source` into `destination`
+///
+/// `mapper` must be the mapper of the active lower half page table.
+pub fn copy_from_user(
+    mapper: &Mapper<RootRecurseLowerHalf>,
+    destination: &mut [u8],
+    source: VirtAddr,
+) -> Result<(), SyscallError> {
+    validate_user_range(mapper, source, destination.len(), false)?;
+
+    // SAFETY: The source range is checked to be user memory above, a fault in the middle of the copy is
+    // recovered by the page fault handler
+    match unsafe { user_copy(destination.as_mut_ptr(), source.as_ptr(), destination.len()) } {
+        0 => Ok(()),
+        _ => Err(SyscallError::BadAddress),
+    }
+}
+
+/// Copy `source` into the user memory at `destination`
+///
+/// `mapper` must be the mapper of the active lower half page table.
+pub fn copy_to_user(
+    mapper: &Mapper<RootRecurseLowerHalf>,
+    destination: VirtAddr,
+    source: &[u8],
+) -> Result<(), SyscallError> {
+    validate_user_range(mapper, destination, source.len(), true)?;
+
+    // SAFETY: The destination range is checked to be writable user memory above, a fault in the middle
+    // of the copy is recovered by the page fault handler
+    match unsafe { user_copy(destination.as_mut_ptr(), source.as_ptr(), source.len()) } {
+        0 => Ok(()),
+        _ => Err(SyscallError::BadAddress),
+    }
+}
+
+/// Called by the page fault handler, returns true if the fault happened while accessing the user
+/// memory in [`user_copy`], in which case the stack frame has been redirected to the fixup path
+pub fn recover_page_fault(stack_frame: &mut ExtendedInterruptStackFrame, error_code: PageFaultErrorCode) -> bool {
+    if error_code.contains(PageFaultErrorCode::USER_MODE) {
+        return false;
+    }
+
+    if stack_frame.instruction_pointer != VirtAddr::new(&raw const user_copy_access as u64) {
+        return false;
+    }
+
+    stack_frame.instruction_pointer = VirtAddr::new(&raw const user_copy_fixup as u64);
+    true
+}
+
+/// Copy `length` bytes from `source` to `destination`, returns the number of bytes left uncopied
+/// (0 on success)
+///
+/// # Safety
+/// One of the range must be a valid kernel range, the other one is allowed to fault, any fault
+/// not coming from the user memory is a bug.
+#[unsafe(naked)]
+unsafe extern "C" fn user_copy(destination: *mut u8, source: *const u8, length: usize) -> usize {
+    naked_asm!(
+        // The copy must go forward even if the direction flag leaked from somewhere
+        "cld",
+        "mov rcx, rdx",
+        ".global user_copy_access",
+        "user_copy_access:",
+        "rep movsb",
+        "xor eax, eax",
+        "ret",
+        ".global user_copy_fixup",
+        "user_copy_fixup:",
+        // rep movsb leaves the remaining count in rcx when it faults
+        "mov rax, rcx",
+        "ret",
+    )
+}
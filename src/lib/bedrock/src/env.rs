@@ -0,0 +1,68 @@
+//! The arguments and the auxiliary vector the program was started with, read from the initial stack
+//! laid out by the kernel.
+
+use core::{
+    ffi::{CStr, c_char},
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use hotline::AuxvType;
+
+/// Points to `argc` on the initial stack, it's never popped since `_start` doesn't return
+static STACK: AtomicPtr<u64> = AtomicPtr::new(null_mut());
+
+/// # Safety
+/// `stack` must point to the initial stack of the program, as laid out by the kernel
+pub(crate) unsafe fn init(stack: *const u64) {
+    STACK.store(stack.cast_mut(), Ordering::Relaxed);
+}
+
+/// The arguments of the program as raw bytes, the first one is the program name
+pub fn args_bytes() -> impl ExactSizeIterator<Item = &'static [u8]> {
+    let stack = STACK.load(Ordering::Relaxed);
+    // SAFETY: The initial stack starts with argc
+    let argc = if stack.is_null() { 0 } else { unsafe { *stack as usize } };
+
+    (0..argc).map(move |index| {
+        // SAFETY: The kernel places argc pointers to NUL terminated strings after argc, they're
+        // never modified
+        unsafe { CStr::from_ptr(*stack.add(1 + index) as *const c_char) }.to_bytes()
+    })
+}
+
+/// The arguments of the program, the first one is the program name
+///
+/// # Panics
+/// If an argument isn't valid utf-8, use [`args_bytes`] instead
+pub fn args() -> impl ExactSizeIterator<Item = &'static str> {
+    args_bytes().map(|arg| core::str::from_utf8(arg).expect("Program argument isn't valid utf-8"))
+}
+
+/// The value of the auxiliary vector entry `typ`, [`None`] if the kernel didn't provide it
+pub fn aux(typ: AuxvType) -> Option<u64> {
+    let stack = STACK.load(Ordering::Relaxed);
+    if stack.is_null() || typ == AuxvType::Null {
+        return None;
+    }
+
+    // SAFETY: The layout is argc, the argv pointers and the envp pointers both ending with NULL,
+    // then the auxiliary vector ending with AuxvType::Null
+    unsafe {
+        let argc = *stack as usize;
+        let mut entry = stack.add(argc + 2);
+        while *entry != 0 {
+            entry = entry.add(1);
+        }
+        entry = entry.add(1);
+
+        while *entry != u64::from(AuxvType::Null) {
+            if *entry == u64::from(typ) {
+                return Some(*entry.add(1));
+            }
+            entry = entry.add(2);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,46 @@
+//! Raw file descriptor operations, see [`hotline::STDIN`], [`hotline::STDOUT`] and [`hotline::STDERR`]
+//! for the descriptors every process starts with.
+
+use hotline::{SyscallError, call};
+
+/// Read into `buffer` from `fd`, blocks until at least a byte is available. Returns the number of
+/// bytes read, 0 at the end of the file (e.g. every write end of a pipe is closed)
+pub fn read(fd: usize, buffer: &mut [u8]) -> Result<usize, SyscallError> {
+    // SAFETY: The buffer is valid for `buffer.len()` bytes
+    unsafe { call::read(fd, buffer.as_mut_ptr() as usize, buffer.len()) }
+}
+
+/// Write a part of `buffer` to `fd`, returns the number of bytes written
+pub fn write(fd: usize, buffer: &[u8]) -> Result<usize, SyscallError> {
+    // SAFETY: The buffer is valid for `buffer.len()` bytes
+    unsafe { call::write(fd, buffer.as_ptr() as usize, buffer.len()) }
+}
+
+/// Write the whole `buffer` to `fd`
+pub fn write_all(fd: usize, mut buffer: &[u8]) -> Result<(), SyscallError> {
+    while !buffer.is_empty() {
+        let written = write(fd, buffer)?;
+        buffer = &buffer[written..];
+    }
+
+    Ok(())
+}
+
+/// Create a pipe, returns its read end and its write end
+pub fn pipe() -> Result<(usize, usize), SyscallError> {
+    let mut fds = [0usize; 2];
+    // SAFETY: The kernel writes two usize to fds
+    unsafe { call::pipe(fds.as_mut_ptr() as usize) }?;
+    Ok((fds[0], fds[1]))
+}
+
+pub fn close(fd: usize) -> Result<(), SyscallError> {
+    // SAFETY: Closing a file descriptor doesn't touch the process memory
+    unsafe { call::close(fd) }
+}
+
+/// Duplicate `fd` into the lowest free file descriptor, returns the new file descriptor
+pub fn dup(fd: usize) -> Result<usize, SyscallError> {
+    // SAFETY: Duplicating a file descriptor doesn't touch the process memory
+    unsafe { call::dup(fd) }
+}
@@ -0,0 +1,155 @@
+//! The global allocator, a linked list allocator that asks the kernel for more memory (See
+//! [`hotline::call::mmap`]) whenever none of its free regions fits the allocation.
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem::{align_of, size_of},
+    ptr,
+};
+
+use hotline::{Protection, call};
+
+/// The page size used by the kernel
+const PAGE_SIZE: usize = 4096;
+
+/// The minimum amount of memory requested from the kernel at once
+const GROW_SIZE: usize = 16 * PAGE_SIZE;
+
+#[global_allocator]
+static HEAP: Heap = Heap::new();
+
+struct Heap {
+    inner: spin::Mutex<LinkedListAllocator>,
+}
+
+impl Heap {
+    const fn new() -> Self {
+        Self { inner: spin::Mutex::new(LinkedListAllocator::new()) }
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (size, align) = LinkedListAllocator::size_align(layout);
+        let mut allocator = self.inner.lock();
+
+        let (region, alloc_start) = match allocator.find_region(size, align) {
+            Some(found) => found,
+            None => {
+                // The extra space makes sure the new region always fits the allocation, leaving room
+                // for the free region node after it
+                if !allocator.grow(size + align + size_of::<ListNode>()) {
+                    return ptr::null_mut();
+                }
+
+                allocator.find_region(size, align).expect("Newly mapped region doesn't fit the allocation")
+            }
+        };
+
+        let alloc_end = alloc_start.checked_add(size).expect("overflow");
+        let excess_size = region.end_addr() - alloc_end;
+        if excess_size > 0 {
+            unsafe { allocator.add_free_region(alloc_end, excess_size) };
+        }
+        alloc_start as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = LinkedListAllocator::size_align(layout);
+        unsafe { self.inner.lock().add_free_region(ptr as usize, size) };
+    }
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+struct ListNode {
+    size: usize,
+    next: Option<&'static mut ListNode>,
+}
+
+impl ListNode {
+    const fn new(size: usize) -> Self {
+        ListNode { size, next: None }
+    }
+
+    fn start_addr(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn end_addr(&self) -> usize {
+        self.start_addr() + self.size
+    }
+}
+
+struct LinkedListAllocator {
+    head: ListNode,
+}
+
+impl LinkedListAllocator {
+    const fn new() -> Self {
+        Self { head: ListNode::new(0) }
+    }
+
+    /// Map at least `min_size` bytes of new memory and add it as a free region
+    fn grow(&mut self, min_size: usize) -> bool {
+        let size = align_up(min_size, PAGE_SIZE).max(GROW_SIZE);
+        // SAFETY: Mapping anonymous memory at a kernel chosen address doesn't affect any existing memory
+        match unsafe { call::mmap(0, size, Protection::READ | Protection::WRITE) } {
+            Ok(start) => {
+                // SAFETY: The region is freshly mapped and not used by anything else
+                unsafe { self.add_free_region(start, size) };
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    unsafe fn add_free_region(&mut self, addr: usize, size: usize) {
+        assert_eq!(align_up(addr, align_of::<ListNode>()), addr);
+        assert!(size >= size_of::<ListNode>());
+
+        let mut node = ListNode::new(size);
+        node.next = self.head.next.take();
+        let node_ptr = addr as *mut ListNode;
+        unsafe { node_ptr.write(node) };
+        self.head.next = Some(unsafe { &mut *node_ptr });
+    }
+
+    fn find_region(&mut self, size: usize, align: usize) -> Option<(&'static mut ListNode, usize)> {
+        let mut current = &mut self.head;
+        while let Some(ref mut region) = current.next {
+            if let Ok(alloc_start) = Self::alloc_from_region(region, size, align) {
+                let next = region.next.take();
+                let ret = Some((current.next.take().unwrap(), alloc_start));
+                current.next = next;
+                return ret;
+            } else {
+                current = current.next.as_mut().unwrap();
+            }
+        }
+
+        None
+    }
+
+    fn alloc_from_region(region: &ListNode, size: usize, align: usize) -> Result<usize, ()> {
+        let alloc_start = align_up(region.start_addr(), align);
+        let alloc_end = alloc_start.checked_add(size).ok_or(())?;
+        if alloc_end > region.end_addr() {
+            return Err(());
+        }
+
+        let excess_size = region.end_addr() - alloc_end;
+        if excess_size > 0 && excess_size < size_of::<ListNode>() {
+            return Err(());
+        }
+        Ok(alloc_start)
+    }
+
+    fn size_align(layout: Layout) -> (usize, usize) {
+        let layout = layout.align_to(align_of::<ListNode>()).expect("adjusting alignment failed").pad_to_align();
+        let size = layout.size().max(size_of::<ListNode>());
+        (size, layout.align())
+    }
+}
@@ -0,0 +1,70 @@
+//! Buffered output to the kernel console.
+//!
+//! The output is line buffered, a line printed with [`println!`](crate::println) is sent to the
+//! kernel at once while holding the [`Stdout`] lock, so lines from different threads don't
+//! interleave.
+
+use core::fmt::{self, Write};
+
+use crate::fd;
+
+const BUFFER_SIZE: usize = 256;
+
+static STDOUT: spin::Mutex<Stdout> = spin::Mutex::new(Stdout::new());
+
+/// A line buffered writer to the kernel console, the buffer always contains valid utf-8
+pub struct Stdout {
+    buffer: [u8; BUFFER_SIZE],
+    len: usize,
+}
+
+impl Stdout {
+    pub(crate) const fn new() -> Self {
+        Self { buffer: [0; BUFFER_SIZE], len: 0 }
+    }
+
+    /// Send the buffered output to the kernel
+    pub fn flush(&mut self) {
+        // SAFETY: Only whole characters are pushed into the buffer
+        let buffered = unsafe { core::str::from_utf8_unchecked(&self.buffer[..self.len]) };
+        write_console(buffered);
+        self.len = 0;
+    }
+}
+
+impl Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.len + c.len_utf8() > BUFFER_SIZE {
+                self.flush();
+            }
+
+            self.len += c.encode_utf8(&mut self.buffer[self.len..]).len();
+
+            if c == '\n' {
+                self.flush();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Lock the process wide [`Stdout`]
+pub fn stdout() -> spin::MutexGuard<'static, Stdout> {
+    STDOUT.lock()
+}
+
+/// Send whatever is left in the process wide [`Stdout`] buffer to the kernel
+pub fn flush() {
+    stdout().flush();
+}
+
+fn write_console(s: &str) {
+    let _ = fd::write_all(hotline::STDOUT, s.as_bytes());
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = stdout().write_fmt(args);
+}
@@ -0,0 +1,49 @@
+//! Message passing through kernel ports, a port is reached through a capability handle.
+
+use hotline::{MESSAGE_SIZE, MessageInfo, NO_CAPABILITY, SyscallError, call};
+
+/// Create a port, returns its receive capability
+pub fn create() -> Result<usize, SyscallError> {
+    // SAFETY: Creating a port doesn't touch the process memory
+    unsafe { call::port_create() }
+}
+
+/// Queue `message` on `port`, transferring a send only copy of `capability` along it. Fails with
+/// [`SyscallError::WouldBlock`] if the port queue is full
+pub fn send(port: usize, message: &[u8; MESSAGE_SIZE], capability: Option<usize>) -> Result<(), SyscallError> {
+    // SAFETY: The message is valid for MESSAGE_SIZE bytes, and no pages are granted
+    unsafe { call::port_send(port, message.as_ptr() as usize, 0, 0, capability.unwrap_or(NO_CAPABILITY)) }
+}
+
+/// Same as [`send`], but moves the `length` bytes (a page aligned range mapped with `mmap`) at
+/// `grant` to the receiver
+///
+/// # Safety
+/// The range is unmapped from the calling process on success, so nothing may reference it anymore
+pub unsafe fn send_grant(
+    port: usize,
+    message: &[u8; MESSAGE_SIZE],
+    grant: *mut u8,
+    length: usize,
+    capability: Option<usize>,
+) -> Result<(), SyscallError> {
+    // SAFETY: The message is valid for MESSAGE_SIZE bytes, the grant is uphold by the caller
+    unsafe {
+        call::port_send(port, message.as_ptr() as usize, grant as usize, length, capability.unwrap_or(NO_CAPABILITY))
+    }
+}
+
+/// Block until a message is queued on `port` (a receive capability), returns its data and info
+pub fn receive(port: usize) -> Result<([u8; MESSAGE_SIZE], MessageInfo), SyscallError> {
+    let mut message = [0; MESSAGE_SIZE];
+    let mut info = MessageInfo::default();
+    // SAFETY: The kernel writes MESSAGE_SIZE bytes to message, and a MessageInfo to info
+    unsafe { call::port_receive(port, message.as_mut_ptr() as usize, &raw mut info as usize) }?;
+    Ok((message, info))
+}
+
+/// Drop the capability `port`, closing the port if it's its receive capability
+pub fn close(port: usize) -> Result<(), SyscallError> {
+    // SAFETY: Dropping a capability doesn't touch the process memory
+    unsafe { call::capability_close(port) }
+}
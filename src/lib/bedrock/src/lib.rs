@@ -0,0 +1,80 @@
+#![no_std]
+
+//! The runtime every userland program is built on.
+//!
+//! Provides the `_start` glue (See [`entry!`]), the program arguments (See [`env`]), a global allocator backed by anonymous memory
+//! mappings, a panic handler that reports the panic and exits the process, and buffered
+//! [`print!`]/[`println!`].
+//!
+//! # Example
+//! ```ignore
+//! #![no_std]
+//! #![no_main]
+//!
+//! bedrock::entry!(main);
+//!
+//! fn main() {
+//!     bedrock::println!("Hello from userland");
+//! }
+//! ```
+
+extern crate alloc;
+
+pub mod env;
+pub mod fd;
+pub mod io;
+pub mod ipc;
+pub mod process;
+pub mod shm;
+pub mod signal;
+pub mod sync;
+pub mod thread;
+pub mod time;
+
+mod heap;
+mod rt;
+
+#[doc(hidden)]
+pub use rt::start;
+
+/// Define the program entry point (`_start`), calling `$main` and exiting the process once it
+/// returns. `_start` passes the initial stack laid out by the kernel (argc, argv, envp and the
+/// auxiliary vector) to the runtime
+#[macro_export]
+macro_rules! entry {
+    ($main:path) => {
+        #[unsafe(no_mangle)]
+        #[unsafe(naked)]
+        pub extern "C" fn _start() -> ! {
+            ::core::arch::naked_asm!(
+                // Terminate the frame pointer chain
+                "xor ebp, ebp",
+                "mov rdi, rsp",
+                "and rsp, -16",
+                "call {start}",
+                "ud2",
+                start = sym __bedrock_start,
+            )
+        }
+
+        extern "C" fn __bedrock_start(stack: *const u64) -> ! {
+            // SAFETY: The stack pointer at `_start` points to the initial stack laid out by the kernel
+            unsafe { $crate::start(stack, $main) }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::io::_print(format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => {
+        $crate::io::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
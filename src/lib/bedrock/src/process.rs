@@ -0,0 +1,84 @@
+use alloc::vec::Vec;
+use hotline::{ANY_CHILD, ProcessInfo, SyscallError, ThreadInfo, call};
+
+use crate::io;
+
+/// The exit code reported when the program panics
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Flush the output and terminate the whole process with the exit `code`
+pub fn exit(code: i32) -> ! {
+    io::flush();
+
+    // SAFETY: The process doesn't hold any resources that needs to be cleaned up
+    let _ = unsafe { call::exit(code) };
+
+    unreachable!("Sys exit doesn't work");
+}
+
+/// Start the program `name` packed with the kernel in a new child process, returns its pid. The
+/// program receives `name` followed by `args` as its arguments (See [`env::args`](crate::env::args))
+pub fn spawn(name: &str, args: &[&str]) -> Result<usize, SyscallError> {
+    let mut packed_args = Vec::with_capacity(args.iter().map(|arg| arg.len() + 1).sum());
+    for arg in args {
+        packed_args.extend_from_slice(arg.as_bytes());
+        packed_args.push(0);
+    }
+
+    // SAFETY: The name and the arguments are valid for their length until the syscall returns
+    unsafe { call::spawn_process(name.as_ptr() as usize, name.len(), packed_args.as_ptr() as usize, packed_args.len()) }
+}
+
+/// Block until the child `pid` exits, returns its exit code
+pub fn wait(pid: usize) -> Result<i32, SyscallError> {
+    wait_raw(pid).map(|(_, code)| code)
+}
+
+/// Block until any child exits, returns its pid and exit code
+pub fn wait_any() -> Result<(usize, i32), SyscallError> {
+    wait_raw(ANY_CHILD)
+}
+
+fn wait_raw(pid: usize) -> Result<(usize, i32), SyscallError> {
+    let mut code = 0i32;
+    // SAFETY: The status points to a valid i32 living until the syscall returns
+    let pid = unsafe { call::wait(pid, &raw mut code as usize) }?;
+    Ok((pid, code))
+}
+
+/// The resources used by the live process `pid`
+pub fn info(pid: usize) -> Result<ProcessInfo, SyscallError> {
+    match next_info(pid)? {
+        info if info.pid == pid => Ok(info),
+        _ => Err(SyscallError::NotFound),
+    }
+}
+
+/// The resources used by every live process, by increasing pid
+pub fn list() -> impl Iterator<Item = ProcessInfo> {
+    let mut pid = 0;
+    core::iter::from_fn(move || {
+        let info = next_info(pid).ok()?;
+        pid = info.pid + 1;
+        Some(info)
+    })
+}
+
+/// The resources used by every thread of the live process `pid`, by increasing thread id
+pub fn threads(pid: usize) -> impl Iterator<Item = ThreadInfo> {
+    let mut thread_id = 0;
+    core::iter::from_fn(move || {
+        let mut info = ThreadInfo::default();
+        // SAFETY: The info points to a valid ThreadInfo living until the syscall returns
+        unsafe { call::thread_info(pid, thread_id, &raw mut info as usize) }.ok()?;
+        thread_id = info.thread_id + 1;
+        Some(info)
+    })
+}
+
+fn next_info(pid: usize) -> Result<ProcessInfo, SyscallError> {
+    let mut info = ProcessInfo::default();
+    // SAFETY: The info points to a valid ProcessInfo living until the syscall returns
+    unsafe { call::process_info(pid, &raw mut info as usize) }?;
+    Ok(info)
+}
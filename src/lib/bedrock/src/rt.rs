@@ -0,0 +1,31 @@
+use core::{fmt::Write, panic::PanicInfo};
+
+use hotline::call;
+
+use crate::{env, io::Stdout, process};
+
+/// Called by the `_start` defined with [`entry!`](crate::entry)
+///
+/// # Safety
+/// `stack` must point to the initial stack of the program, as laid out by the kernel
+pub unsafe fn start(stack: *const u64, main: fn()) -> ! {
+    // SAFETY: Guaranteed by the caller
+    unsafe { env::init(stack) };
+    main();
+    process::exit(0)
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // The panicking thread might be holding the process wide stdout lock, so don't touch it at all
+    let mut out = Stdout::new();
+    let _ = writeln!(out, "{info}");
+    out.flush();
+
+    // SAFETY: The process is going away, nothing can be cleaned up anymore
+    let _ = unsafe { call::exit(process::PANIC_EXIT_CODE) };
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,32 @@
+//! Shared memory objects, mapped by several processes at once. An object is reached through a
+//! capability handle, which can be transferred through a port (See [`ipc`](crate::ipc)).
+
+use hotline::{Protection, SyscallError, call};
+
+/// Create a shared memory object of `length` bytes (rounded up to pages) of zeroed memory, returns
+/// its capability
+pub fn create(length: usize) -> Result<usize, SyscallError> {
+    // SAFETY: Creating an object doesn't touch the process memory
+    unsafe { call::shm_create(length) }
+}
+
+/// Map the whole object behind `handle` anywhere in the process, returns the start of the mapping
+pub fn map(handle: usize, protection: Protection) -> Result<*mut u8, SyscallError> {
+    // SAFETY: The kernel picks a free range, so no existing memory is touched
+    unsafe { call::shm_map(handle, 0, protection) }.map(|address| address as *mut u8)
+}
+
+/// Unmap the mapping of `length` bytes at `address` returned by [`map`]
+///
+/// # Safety
+/// Nothing may reference the mapping anymore
+pub unsafe fn unmap(address: *mut u8, length: usize) -> Result<(), SyscallError> {
+    // SAFETY: Uphold by the caller
+    unsafe { call::munmap(address as usize, length) }
+}
+
+/// Drop the capability `handle`, the existing mappings stay valid
+pub fn close(handle: usize) -> Result<(), SyscallError> {
+    // SAFETY: Dropping a capability doesn't touch the process memory
+    unsafe { call::capability_close(handle) }
+}
@@ -0,0 +1,52 @@
+//! Signals, asynchronous notifications sent to a process or to one of its threads.
+
+use hotline::{SIGNAL_DEFAULT, SIGNAL_IGNORE, Signal, SyscallError, SyscallNumber, call};
+
+/// Run `handler` on a thread of the process when it receives `signal`. The handler interrupts the
+/// thread wherever it is, so it should only touch atomics
+pub fn set_handler(signal: Signal, handler: extern "C" fn(Signal)) -> Result<(), SyscallError> {
+    action(signal, handler as usize)
+}
+
+/// Discard `signal` when it's received
+pub fn ignore(signal: Signal) -> Result<(), SyscallError> {
+    action(signal, SIGNAL_IGNORE)
+}
+
+/// Restore the default action of `signal`
+pub fn reset(signal: Signal) -> Result<(), SyscallError> {
+    action(signal, SIGNAL_DEFAULT)
+}
+
+/// Send `signal` to the process `pid`
+pub fn kill(pid: usize, signal: Signal) -> Result<(), SyscallError> {
+    // SAFETY: Sending a signal doesn't touch the process memory
+    unsafe { call::kill(pid, signal) }
+}
+
+/// Send `signal` to the thread `id` (returned by [`thread::spawn`](crate::thread::spawn)) of the
+/// current process
+pub fn thread_kill(id: usize, signal: Signal) -> Result<(), SyscallError> {
+    // SAFETY: Sending a signal doesn't touch the process memory
+    unsafe { call::thread_kill(id, signal) }
+}
+
+fn action(signal: Signal, handler: usize) -> Result<(), SyscallError> {
+    // SAFETY: The trampoline follows the contract of signal_action
+    unsafe { call::signal_action(signal, handler, trampoline as *const () as usize) }.map(|_| ())
+}
+
+/// Entered by the kernel with the signal in `rdi`, the handler in `rsi` and the saved state in `rdx`
+/// (also the stack pointer), rbx is free to use since the saved state is restored as a whole
+#[unsafe(naked)]
+extern "C" fn trampoline() -> ! {
+    core::arch::naked_asm!(
+        "mov rbx, rdx",
+        "call rsi",
+        "mov rdx, rbx",
+        "mov eax, {signal_return}",
+        "syscall",
+        "ud2",
+        signal_return = const SyscallNumber::SignalReturn as u32,
+    )
+}
@@ -0,0 +1,25 @@
+use core::sync::atomic::AtomicU32;
+
+use hotline::{SyscallError, call};
+
+/// Block the current thread until [`futex_wake`] is called on the same atomic, returns right away
+/// with [`SyscallError::WouldBlock`] if the atomic doesn't hold `expected`. Spurious wake ups are
+/// possible, the condition must be checked again once this returns.
+pub fn futex_wait(futex: &AtomicU32, expected: u32) -> Result<(), SyscallError> {
+    // SAFETY: The address points to a live, aligned u32 until the syscall returns
+    unsafe { call::futex_wait(futex.as_ptr() as usize, expected) }
+}
+
+/// Same as [`futex_wait`], but returns [`SyscallError::TimedOut`] once the thread was blocked for
+/// `millis` milliseconds without being woken up
+pub fn futex_wait_timeout(futex: &AtomicU32, expected: u32, millis: usize) -> Result<(), SyscallError> {
+    // SAFETY: The address points to a live, aligned u32 until the syscall returns
+    unsafe { call::futex_wait_timeout(futex.as_ptr() as usize, expected, millis) }
+}
+
+/// Wake up to `count` threads blocked in [`futex_wait`] on the atomic, returns the number of threads
+/// woken up
+pub fn futex_wake(futex: &AtomicU32, count: usize) -> usize {
+    // SAFETY: The address is only used as a key, it's never dereferenced by the kernel
+    unsafe { call::futex_wake(futex.as_ptr() as usize, count) }.unwrap_or_default()
+}
@@ -0,0 +1,40 @@
+use hotline::{SyscallError, call};
+
+/// Spawn a new thread in the current process running `entry`, returns the id of the new thread
+pub fn spawn(entry: fn() -> !) -> Result<usize, SyscallError> {
+    // SAFETY: The entry is a valid function in this process
+    unsafe { call::spawn(entry as *const () as usize) }
+}
+
+/// Block until the thread `id` (returned by [`spawn`]) of the current process exits
+pub fn join(id: usize) -> Result<(), SyscallError> {
+    // SAFETY: Join doesn't have any requirements
+    unsafe { call::join(id) }
+}
+
+/// Put the current thread to sleep for at least `millis` milliseconds
+pub fn sleep(millis: usize) {
+    // SAFETY: Sleep doesn't have any requirements
+    let _ = unsafe { call::sleep(millis) };
+}
+
+/// Let the other ready threads run before the current one continues, without sleeping
+pub fn yield_now() {
+    // SAFETY: Yield doesn't have any requirements
+    let _ = unsafe { call::yield_now() };
+}
+
+/// Set the nice value of the current thread, from [`hotline::MIN_NICE`] (scheduled first) to
+/// [`hotline::MAX_NICE`]
+pub fn set_priority(nice: i8) -> Result<(), SyscallError> {
+    // SAFETY: Setting the priority doesn't touch the process memory
+    unsafe { call::set_priority(nice) }
+}
+
+/// Terminate the current thread
+pub fn exit() -> ! {
+    // SAFETY: The thread doesn't hold any resources that needs to be cleaned up
+    let _ = unsafe { call::exit_thread() };
+
+    unreachable!("Sys exit thread doesn't work");
+}
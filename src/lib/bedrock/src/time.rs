@@ -0,0 +1,21 @@
+//! The kernel clocks, See [`hotline::Clock`].
+
+use core::time::Duration;
+
+use hotline::{Clock, call};
+
+/// The time elapsed since the boot, never goes backward
+pub fn monotonic() -> Duration {
+    now(Clock::Monotonic)
+}
+
+/// The time elapsed since the unix epoch (UTC)
+pub fn realtime() -> Duration {
+    now(Clock::Realtime)
+}
+
+fn now(clock: Clock) -> Duration {
+    // SAFETY: Reading a clock doesn't touch the process memory
+    let nanos = unsafe { call::clock_get(clock) }.expect("The kernel doesn't support the clock");
+    Duration::from_nanos(nanos)
+}
@@ -0,0 +1,645 @@
+#![no_std]
+
+//! The system call ABI shared between the kernel and the userland programs.
+//!
+//! Every system call is declared exactly once in the table at the bottom of this file, both the
+//! kernel decoder ([`Syscall::decode`]) and the userland stubs (`hotline::call`, behind the
+//! `userland` feature) are generated from it, so the two sides can't drift apart.
+//!
+//! # Calling convention
+//! - `rax` holds the [`SyscallNumber`] on entry, and the encoded result on return.
+//! - The arguments are passed in [`ARGUMENT_REGISTERS`] order, unused argument registers are ignored.
+//! - `rcx` and `r11` are clobbered by the `syscall` instruction itself, every other register is preserved.
+//! - A result in the range `[-4095, -1]` (as an i64) is an error, See [`SyscallError`] and [`decode_result`].
+
+use bitflags::bitflags;
+use c_enum::c_enum;
+
+/// The version of the ABI described by this crate, bumped every time an existing syscall changes
+/// its number, arguments or return value. Adding a new syscall doesn't bump the version.
+pub const ABI_VERSION: u32 = 3;
+
+/// The maximum number of arguments a syscall can take
+pub const ARGUMENT_COUNT: usize = 6;
+
+/// The largest error code that can be encoded in `rax`
+pub const MAX_ERROR: u64 = 4095;
+
+/// The file descriptor of the standard input, every process starts with it opened to the console
+pub const STDIN: usize = 0;
+
+/// The file descriptor of the standard output, every process starts with it opened to the console
+pub const STDOUT: usize = 1;
+
+/// The file descriptor of the standard error, every process starts with it opened to the console
+pub const STDERR: usize = 2;
+
+/// Passed as the pid to `wait` to wait for any child of the calling process
+pub const ANY_CHILD: usize = usize::MAX;
+
+/// The exit code of a process killed by the kernel (e.g. after an unrecoverable fault) or by a signal
+pub const KILLED_EXIT_CODE: i32 = -1;
+
+/// Passed as the handler to `signal_action` to restore the default action of a signal
+pub const SIGNAL_DEFAULT: usize = 0;
+
+/// Passed as the handler to `signal_action` to discard a signal
+pub const SIGNAL_IGNORE: usize = 1;
+
+/// The nice value of the threads scheduled first, see `set_priority`
+pub const MIN_NICE: i8 = -20;
+
+/// The nice value of the threads scheduled last, see `set_priority`
+pub const MAX_NICE: i8 = 19;
+
+/// The nice value every thread starts with
+pub const DEFAULT_NICE: i8 = 0;
+
+/// The size of the data carried by every message sent through a port
+pub const MESSAGE_SIZE: usize = 64;
+
+/// Passed as the capability to `port_send` when the message doesn't transfer one, and reported in
+/// [`MessageInfo::capability`] when the received message didn't carry one
+pub const NO_CAPABILITY: usize = usize::MAX;
+
+/// A register used to pass a syscall argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentRegister {
+    Rdx,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+}
+
+/// The registers used to pass the arguments, the first argument is passed in the first register
+pub const ARGUMENT_REGISTERS: [ArgumentRegister; ARGUMENT_COUNT] = [
+    ArgumentRegister::Rdx,
+    ArgumentRegister::Rsi,
+    ArgumentRegister::Rdi,
+    ArgumentRegister::R8,
+    ArgumentRegister::R9,
+    ArgumentRegister::R10,
+];
+
+c_enum! {
+    /// An error returned by the kernel, encoded as the negated value in `rax`
+    pub enum SyscallError: u64 {
+        // The syscall number is not known by the kernel
+        UnknownSyscall      = 1
+        // One of the argument can't be decoded or is out of the accepted range
+        InvalidArgument     = 2
+        // An address argument points to memory the caller isn't allowed to use (e.g. the higher half)
+        BadAddress          = 3
+        // The kernel ran out of physical memory while serving the request
+        OutOfMemory         = 4
+        // The requested address range overlaps with an existing mapping
+        AddressInUse        = 5
+        // The file descriptor isn't open, or doesn't support the operation
+        BadFileDescriptor   = 6
+        // The calling process doesn't have a child matching the request
+        NoChild             = 7
+        // The requested object (e.g. a program) doesn't exist
+        NotFound            = 8
+        // The program isn't a valid executable
+        InvalidExecutable   = 9
+        // The value observed by the kernel didn't match the expected one (e.g. a futex), retry
+        WouldBlock          = 10
+        // Writing to a pipe whose read end is closed
+        BrokenPipe          = 11
+        // The process file descriptor table is full
+        TooManyFiles        = 12
+        // The capability handle isn't held by the process, or doesn't allow the operation
+        InvalidCapability   = 13
+        // The port was closed by the process holding its receive capability
+        PortClosed          = 14
+        // The process capability table is full
+        TooManyCapabilities = 15
+        // The syscall blocked for longer than its timeout
+        TimedOut            = 16
+    }
+}
+
+c_enum! {
+    /// The type of an auxiliary vector entry, the vector is placed on the initial stack of a program
+    /// after the environment pointers, and ends with [`AuxvType::Null`]
+    pub enum AuxvType: u64 {
+        // The end of the vector
+        Null               = 0
+        // The address of the program headers of the loaded program
+        ProgramHeaders     = 3
+        // The size of a single program header
+        ProgramHeaderSize  = 4
+        // The number of program headers
+        ProgramHeaderCount = 5
+        // The size of a page
+        PageSize           = 6
+        // The entry point of the program
+        Entry              = 9
+        // The address of 16 random bytes
+        Random             = 25
+    }
+}
+
+impl SyscallError {
+    /// Encode the error into the value written to `rax`
+    pub const fn encode(self) -> u64 {
+        (self.0 as i64).wrapping_neg() as u64
+    }
+}
+
+/// Encode a syscall result into the value written to `rax`
+///
+/// A successful value must not fall in the error range (`[-4095, -1]` as i64), the kernel never
+/// returns such values since they would be a higher half address.
+pub const fn encode_result(result: Result<u64, SyscallError>) -> u64 {
+    match result {
+        Ok(value) => {
+            debug_assert!(value <= u64::MAX - MAX_ERROR, "Syscall result is in the error range");
+            value
+        }
+        Err(error) => error.encode(),
+    }
+}
+
+/// Decode the value returned in `rax`
+pub const fn decode_result(raw: u64) -> Result<u64, SyscallError> {
+    if raw > u64::MAX - MAX_ERROR { Err(SyscallError((raw as i64).wrapping_neg() as u64)) } else { Ok(raw) }
+}
+
+/// A type that can be passed through a single register, either as an argument or as a return value
+pub trait SyscallArg: Sized {
+    fn into_raw(self) -> u64;
+
+    fn from_raw(raw: u64) -> Result<Self, SyscallError>;
+}
+
+macro_rules! impl_integer_arg {
+    ($($ty:ty)*) => {
+        $(
+            impl SyscallArg for $ty {
+                fn into_raw(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+                    raw.try_into().map_err(|_| SyscallError::InvalidArgument)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_arg!(u8 u16 u32 u64 usize);
+
+macro_rules! impl_signed_integer_arg {
+    ($($ty:ty)*) => {
+        $(
+            impl SyscallArg for $ty {
+                fn into_raw(self) -> u64 {
+                    self as i64 as u64
+                }
+
+                fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+                    (raw as i64).try_into().map_err(|_| SyscallError::InvalidArgument)
+                }
+            }
+        )*
+    };
+}
+
+impl_signed_integer_arg!(i8 i16 i32 i64 isize);
+
+impl SyscallArg for () {
+    fn into_raw(self) -> u64 {
+        0
+    }
+
+    fn from_raw(_raw: u64) -> Result<Self, SyscallError> {
+        Ok(())
+    }
+}
+
+impl SyscallArg for bool {
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+
+    fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+        match raw {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SyscallError::InvalidArgument),
+        }
+    }
+}
+
+impl SyscallArg for char {
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+
+    fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+        u32::try_from(raw).ok().and_then(char::from_u32).ok_or(SyscallError::InvalidArgument)
+    }
+}
+
+bitflags! {
+    /// The access allowed on a memory mapping
+    ///
+    /// # Note
+    /// Every page is readable by the hardware once it's accessible at all, so [`Protection::WRITE`] and
+    /// [`Protection::EXECUTE`] imply [`Protection::READ`], an empty protection makes the mapping inaccessible.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Protection: u64 {
+        const READ =    1 << 0;
+        const WRITE =   1 << 1;
+        const EXECUTE = 1 << 2;
+    }
+}
+
+impl SyscallArg for Protection {
+    fn into_raw(self) -> u64 {
+        self.bits()
+    }
+
+    fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+        Self::from_bits(raw).ok_or(SyscallError::InvalidArgument)
+    }
+}
+
+/// Written by `port_receive` next to the message data, describes where the message came from and
+/// what it carried along
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageInfo {
+    /// The pid of the sending process
+    pub sender: usize,
+    /// The address the granted pages were mapped at in the receiver, 0 if the message didn't grant any
+    pub grant: usize,
+    /// The length of the granted range, a multiple of the page size
+    pub grant_length: usize,
+    /// The capability transferred with the message, [`NO_CAPABILITY`] if it didn't carry one
+    pub capability: usize,
+}
+
+impl MessageInfo {
+    pub const SIZE: usize = size_of::<Self>();
+
+    pub fn to_ne_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let fields = [self.sender, self.grant, self.grant_length, self.capability];
+        for (chunk, field) in bytes.chunks_exact_mut(size_of::<usize>()).zip(fields) {
+            chunk.copy_from_slice(&field.to_ne_bytes());
+        }
+        bytes
+    }
+}
+
+/// A clock readable with `clock_get`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum Clock {
+    /// Counts from the boot, never goes backward and isn't affected by the wall clock
+    Monotonic = 0,
+    /// The wall clock, counts from the unix epoch (UTC)
+    Realtime = 1,
+}
+
+impl SyscallArg for Clock {
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+
+    fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+        match raw {
+            0 => Ok(Self::Monotonic),
+            1 => Ok(Self::Realtime),
+            _ => Err(SyscallError::InvalidArgument),
+        }
+    }
+}
+
+/// An asynchronous notification sent to a process with `kill`, or to a thread with `thread_kill`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum Signal {
+    /// Terminates the process, can't be handled or ignored
+    Kill = 0,
+    /// A request to terminate, terminates the process by default
+    Terminate = 1,
+    /// An interruption from the user, terminates the process by default
+    Interrupt = 2,
+    /// Free for the programs to use, terminates the process by default
+    User = 3,
+    /// Sent to the parent of a process when it exits, ignored by default
+    Child = 4,
+}
+
+impl Signal {
+    /// The number of signals
+    pub const COUNT: usize = 5;
+
+    /// Every signal, in order
+    pub const ALL: [Self; Self::COUNT] = [Self::Kill, Self::Terminate, Self::Interrupt, Self::User, Self::Child];
+
+    /// Whether the signal terminates the process when it isn't handled, it's ignored otherwise
+    pub const fn terminates_by_default(self) -> bool {
+        !matches!(self, Self::Child)
+    }
+}
+
+impl SyscallArg for Signal {
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+
+    fn from_raw(raw: u64) -> Result<Self, SyscallError> {
+        usize::try_from(raw).ok().and_then(|raw| Self::ALL.get(raw).copied()).ok_or(SyscallError::InvalidArgument)
+    }
+}
+
+/// Written by `process_info`, the resources used by a process
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: usize,
+    /// The time spent running the threads of the process (including the exited ones) in nanoseconds,
+    /// the time is accounted in milliseconds so it's always a multiple of 1 ms
+    pub cpu_time: u64,
+    pub threads: usize,
+    /// The pages mapped with `mmap`, `shm_map` or received as a grant, along with the thread stacks. The
+    /// program image isn't counted
+    pub mapped_pages: usize,
+    /// The thread stacks allocated in the process
+    pub stacks: usize,
+}
+
+impl ProcessInfo {
+    pub const SIZE: usize = size_of::<Self>();
+
+    pub fn to_ne_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let fields =
+            [self.pid as u64, self.cpu_time, self.threads as u64, self.mapped_pages as u64, self.stacks as u64];
+        for (chunk, field) in bytes.chunks_exact_mut(size_of::<u64>()).zip(fields) {
+            chunk.copy_from_slice(&field.to_ne_bytes());
+        }
+        bytes
+    }
+}
+
+/// Written by `thread_info`, the resources used by a thread
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreadInfo {
+    pub thread_id: usize,
+    /// The time spent running the thread in nanoseconds, the time is accounted in milliseconds so it's
+    /// always a multiple of 1 ms
+    pub cpu_time: u64,
+}
+
+impl ThreadInfo {
+    pub const SIZE: usize = size_of::<Self>();
+
+    pub fn to_ne_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let fields = [self.thread_id as u64, self.cpu_time];
+        for (chunk, field) in bytes.chunks_exact_mut(size_of::<u64>()).zip(fields) {
+            chunk.copy_from_slice(&field.to_ne_bytes());
+        }
+        bytes
+    }
+}
+
+/// Pads the arguments to [`ARGUMENT_COUNT`]
+#[doc(hidden)]
+pub fn pad_arguments<const N: usize>(args: [u64; N]) -> [u64; ARGUMENT_COUNT] {
+    let mut padded = [0; ARGUMENT_COUNT];
+    padded[..N].copy_from_slice(&args);
+    padded
+}
+
+/// Invoke a syscall with the raw number and the raw arguments, returning the raw `rax`
+///
+/// # Safety
+/// The caller must uphold the contract of the syscall being invoked, e.g. pointers passed must be
+/// valid for the kernel to read or write.
+#[cfg(feature = "userland")]
+#[inline(always)]
+pub unsafe fn raw_syscall(number: u64, args: [u64; ARGUMENT_COUNT]) -> u64 {
+    let result: u64;
+    // SAFETY: The kernel preserves every register except rax (result), rcx and r11 (clobbered by
+    // the syscall instruction). The argument order must match ARGUMENT_REGISTERS.
+    unsafe {
+        core::arch::asm!(
+            "syscall",
+            inlateout("rax") number => result,
+            in("rdx") args[0],
+            in("rsi") args[1],
+            in("rdi") args[2],
+            in("r8") args[3],
+            in("r9") args[4],
+            in("r10") args[5],
+            out("rcx") _,
+            out("r11") _,
+            options(nostack),
+        );
+    }
+    result
+}
+
+macro_rules! syscalls {
+    (
+        $(
+            $(#[$meta:meta])*
+            $number:literal => $name:ident as $fn_name:ident($($arg:ident: $arg_ty:ty),* $(,)?) -> $ret:ty;
+        )*
+    ) => {
+        /// Every syscall number known by this version of the ABI
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u64)]
+        pub enum SyscallNumber {
+            $(
+                $(#[$meta])*
+                $name = $number,
+            )*
+        }
+
+        impl TryFrom<u64> for SyscallNumber {
+            type Error = SyscallError;
+
+            fn try_from(value: u64) -> Result<Self, Self::Error> {
+                match value {
+                    $($number => Ok(Self::$name),)*
+                    _ => Err(SyscallError::UnknownSyscall),
+                }
+            }
+        }
+
+        /// A decoded syscall, with the arguments converted to their declared types
+        #[derive(Debug, Clone, Copy)]
+        pub enum Syscall {
+            $(
+                $(#[$meta])*
+                $name { $($arg: $arg_ty),* },
+            )*
+        }
+
+        impl Syscall {
+            /// Decode the syscall from the raw number and the raw arguments (in [`ARGUMENT_REGISTERS`] order)
+            pub fn decode(number: u64, args: [u64; ARGUMENT_COUNT]) -> Result<Self, SyscallError> {
+                #[allow(unused_mut, unused_variables)]
+                let mut args = args.into_iter();
+                match SyscallNumber::try_from(number)? {
+                    $(
+                        SyscallNumber::$name => Ok(Self::$name {
+                            $($arg: <$arg_ty as SyscallArg>::from_raw(args.next().unwrap_or_default())?),*
+                        }),
+                    )*
+                }
+            }
+
+            pub fn number(&self) -> SyscallNumber {
+                match self {
+                    $(Self::$name { .. } => SyscallNumber::$name,)*
+                }
+            }
+        }
+
+        /// Userland stubs, one per syscall, See the syscall documentation for the contract.
+        #[cfg(feature = "userland")]
+        pub mod call {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $(
+                $(#[$meta])*
+                ///
+                /// # Safety
+                /// The caller must uphold the contract of this syscall.
+                #[inline]
+                pub unsafe fn $fn_name($($arg: $arg_ty),*) -> Result<$ret, SyscallError> {
+                    let args = pad_arguments([$(SyscallArg::into_raw($arg)),*]);
+                    // SAFETY: The contract is uphold by the caller
+                    let raw = unsafe { raw_syscall($number, args) };
+                    <$ret as SyscallArg>::from_raw(decode_result(raw)?)
+                }
+            )*
+        }
+    };
+}
+
+syscalls! {
+    /// Terminate the calling process with the exit `code`, never returns on success
+    0 => Exit as exit(code: i32) -> ();
+    /// Put the calling thread to sleep for at least `millis` milliseconds
+    1 => Sleep as sleep(millis: usize) -> ();
+    /// Spawn a new thread in the calling process starting at `entry`, returns the id of the new thread
+    2 => Spawn as spawn(entry: usize) -> usize;
+    /// Terminate the calling thread, never returns on success
+    3 => ExitThread as exit_thread() -> ();
+    /// Print a single character to the serial port
+    4 => Test as test(c: char) -> ();
+    /// Dump the kernel debug counters and flush the kernel log
+    5 => Flush as flush() -> ();
+    /// Returns the [`ABI_VERSION`] implemented by the kernel
+    6 => AbiVersion as abi_version() -> u32;
+    /// Map `length` bytes (rounded up to pages) of zeroed anonymous memory, at `address` or anywhere
+    /// if `address` is 0, returns the start of the mapping
+    7 => MemoryMap as mmap(address: usize, length: usize, protection: Protection) -> usize;
+    /// Unmap a range previously mapped with `mmap` or `shm_map`, the range must be fully mapped and
+    /// can't cover a `shm_map` mapping partially
+    8 => MemoryUnmap as munmap(address: usize, length: usize) -> ();
+    /// Change the protection of a range previously mapped with `mmap`, the range must be fully mapped
+    9 => MemoryProtect as mprotect(address: usize, length: usize, protection: Protection) -> ();
+    /// Write `length` bytes from `buffer` to the file descriptor `fd`, returns the number of bytes
+    /// written. Writing to a full pipe blocks until at least a byte can be written
+    10 => Write as write(fd: usize, buffer: usize, length: usize) -> usize;
+    /// Block until the child `pid` (or any child with [`ANY_CHILD`]) exits, writes its exit code (an
+    /// i32) to `status` unless it's 0, returns the pid of the collected child
+    11 => Wait as wait(pid: usize, status: usize) -> usize;
+    /// Block until the thread `thread_id` of the calling process exits, returns right away if it
+    /// already exited
+    12 => Join as join(thread_id: usize) -> ();
+    /// Start the program `name` (utf-8) from the programs packed with the kernel in a new child
+    /// process, `args` is a buffer of NUL terminated arguments. The program receives `name` followed
+    /// by `args` on its initial stack, see [`AuxvType`]. Returns the pid of the new process
+    13 => SpawnProcess as spawn_process(name: usize, name_length: usize, args: usize, args_length: usize) -> usize;
+    /// Block the calling thread until it's woken up by `futex_wake` on the same `address` (a 4 bytes
+    /// aligned u32), fails with [`SyscallError::WouldBlock`] if the value at `address` isn't `expected`
+    14 => FutexWait as futex_wait(address: usize, expected: u32) -> ();
+    /// Wake up to `count` threads of the calling process blocked in `futex_wait` on `address`, returns
+    /// the number of threads woken up
+    15 => FutexWake as futex_wake(address: usize, count: usize) -> usize;
+    /// Read up to `length` bytes from the file descriptor `fd` into `buffer`, blocks until at least a
+    /// byte is available. Returns the number of bytes read, 0 once every write end of a pipe is closed
+    16 => Read as read(fd: usize, buffer: usize, length: usize) -> usize;
+    /// Create a pipe, writes the read end then the write end file descriptors (two usize) to `fds`.
+    /// File descriptors are inherited by the processes spawned afterwards, at the same numbers
+    17 => Pipe as pipe(fds: usize) -> ();
+    /// Close the file descriptor `fd`, a pipe end is closed once every descriptor referring to it is
+    /// closed
+    18 => Close as close(fd: usize) -> ();
+    /// Duplicate the file descriptor `fd` into the lowest free one, returns the new file descriptor
+    19 => Dup as dup(fd: usize) -> usize;
+    /// Create a port owned by the calling process, returns a capability allowing to receive from (and
+    /// send to) it. Capabilities are inherited by the processes spawned afterwards, port capabilities
+    /// are inherited as send only
+    20 => PortCreate as port_create() -> usize;
+    /// Queue a message of [`MESSAGE_SIZE`] bytes read from `message` on the port behind the capability
+    /// `port`, fails with [`SyscallError::WouldBlock`] if the port queue is full. `grant_length` bytes
+    /// (a page aligned range mapped with `mmap`) at `grant` are moved to the receiver unless
+    /// `grant_length` is 0, and the capability `capability` is copied (as send only for a port) unless
+    /// it's [`NO_CAPABILITY`]
+    21 => PortSend as port_send(port: usize, message: usize, grant: usize, grant_length: usize, capability: usize) -> ();
+    /// Block until a message is queued on the port behind the receive capability `port`, writes its
+    /// data to `message` ([`MESSAGE_SIZE`] bytes) and its [`MessageInfo`] to `info`
+    22 => PortReceive as port_receive(port: usize, message: usize, info: usize) -> ();
+    /// Drop the capability `handle`, closing the receive capability of a port closes the port and
+    /// drops the messages still queued on it
+    23 => CapabilityClose as capability_close(handle: usize) -> ();
+    /// Create a shared memory object of `length` bytes (rounded up to pages, at most 64 MiB) of zeroed
+    /// memory, returns its capability. The memory is freed once every capability and every mapping of
+    /// it is gone
+    24 => SharedMemoryCreate as shm_create(length: usize) -> usize;
+    /// Map the whole shared memory object behind the capability `handle`, at `address` or anywhere if
+    /// `address` is 0, returns the start of the mapping. The mapping is removed with `munmap`
+    25 => SharedMemoryMap as shm_map(handle: usize, address: usize, protection: Protection) -> usize;
+    /// Returns the current time of `clock` in nanoseconds
+    26 => ClockGet as clock_get(clock: Clock) -> u64;
+    /// Give up the rest of the time slice of the calling thread, it runs again once every other ready
+    /// thread of its core got to run
+    27 => Yield as yield_now() -> ();
+    /// Write the [`ProcessInfo`] of the live process with the lowest pid greater or equal to `pid` to
+    /// `info`, returns its pid. Fails with [`SyscallError::NotFound`] when there's none, every process
+    /// is listed by starting at 0 and passing the returned pid + 1
+    28 => ProcessInfo as process_info(pid: usize, info: usize) -> usize;
+    /// Same as `process_info` for the threads of the live process `pid`, writes the [`ThreadInfo`] of
+    /// the thread with the lowest id greater or equal to `thread_id` to `info`, returns its id
+    29 => ThreadInfo as thread_info(pid: usize, thread_id: usize, info: usize) -> usize;
+    /// Set the action taken when the calling process receives `signal`, returns the previous handler.
+    /// `handler` is either [`SIGNAL_DEFAULT`], [`SIGNAL_IGNORE`] or the address of a function, which
+    /// is run by a thread of the process on its next return to userland: the thread resumes at
+    /// `trampoline` with the signal in `rdi`, `handler` in `rsi`, and the address of its saved state in
+    /// `rdx` (also the 16 bytes aligned stack pointer). The trampoline calls the handler, then
+    /// `signal_return` with the saved state. [`Signal::Kill`] can't be handled
+    30 => SignalAction as signal_action(signal: Signal, handler: usize, trampoline: usize) -> usize;
+    /// Send `signal` to the process `pid`, it's handled by any of its threads
+    31 => Kill as kill(pid: usize, signal: Signal) -> ();
+    /// Send `signal` to the thread `thread_id` of the calling process, a signal terminating the thread
+    /// terminates its whole process
+    32 => ThreadKill as thread_kill(thread_id: usize, signal: Signal) -> ();
+    /// Resume the calling thread with the state saved at `frame` when a signal handler was invoked,
+    /// never returns on success
+    33 => SignalReturn as signal_return(frame: usize) -> ();
+    /// Set the nice value of the calling thread, from [`MIN_NICE`] (scheduled first) to [`MAX_NICE`].
+    /// A thread starts at the priority given by its nice value and drops a level each time it uses
+    /// its whole time slice, it's moved to the top level periodically and back to its base level once
+    /// it wakes up from a sleep or a blocking syscall
+    34 => SetPriority as set_priority(nice: i8) -> ();
+    /// Same as `futex_wait`, but fails with [`SyscallError::TimedOut`] once the calling thread has
+    /// been blocked for `millis` milliseconds without being woken up
+    35 => FutexWaitTimeout as futex_wait_timeout(address: usize, expected: u32, millis: usize) -> ();
+}
@@ -64,6 +64,16 @@ impl<S: PageSize> Frame<S> {
     {
         self.into()
     }
+
+    /// Same as [`Self::erase`] for any page size, the variant is picked from [`PageSize::LEVEL`]
+    pub const fn erase_level(self) -> AnyFrame {
+        let start = self.start_address();
+        match S::LEVEL {
+            PageLevel::Page4K => AnyFrame::Frame4K(Frame::containing_address(start)),
+            PageLevel::Page2M => AnyFrame::Frame2M(Frame::containing_address(start)),
+            PageLevel::Page1G => AnyFrame::Frame1G(Frame::containing_address(start)),
+        }
+    }
 }
 
 impl<S: PageSize> From<Frame<S>> for PhysAddr {
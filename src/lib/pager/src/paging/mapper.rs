@@ -6,9 +6,9 @@ use crate::paging::table::{DirectCreate, RecurseCreate, RootLevel, TableLevel};
 use crate::registers::tlb;
 use crate::{PageLevel, any_frame_select, any_page_select};
 
-use super::EntryFlags;
 use super::table::Table;
-use core::ptr::NonNull;
+use super::{ENTRY_COUNT, EntryFlags};
+use core::{ops::Range, ptr::NonNull};
 
 pub struct MapperWithAllocator<'a, Root: RootLevel, A: FrameAllocator> {
     pub mapper: &'a mut Mapper<Root>,
@@ -61,6 +61,11 @@ impl<'a, Root: RootLevel, A: FrameAllocator> MapperWithAllocator<'a, Root, A> {
         self.mapper.translate_page(page)
     }
 
+    /// Just a mirror; see [`Mapper::translate_page_flags`].
+    pub fn translate_page_flags<S: PageSize>(&self, page: Page<S>) -> Option<EntryFlags> {
+        self.mapper.translate_page_flags(page)
+    }
+
     /// Just a mirror; see [`Mapper::change_flags`].
     ///
     /// # Safety
@@ -363,6 +368,28 @@ impl<Root: RootLevel> Mapper<Root> {
         get(&p1[page.p1_index() as usize])
     }
 
+    /// Get the flags of the entry mapping the provided page, the entry can be a huge page entry if the
+    /// page is part of a huge page
+    ///
+    /// If the page is not mapped, will return none
+    pub fn translate_page_flags<S: PageSize>(&self, page: Page<S>) -> Option<EntryFlags> {
+        let p3 = self.p4().next_table(page.p4_index())?;
+
+        fn get<L: TableLevel>(entry: &Entry<L>) -> Option<EntryFlags> {
+            Some(entry.flags()).filter(|flags| flags.contains(EntryFlags::PRESENT))
+        }
+
+        let Some(p2) = p3.next_table(page.p3_index()) else {
+            return get(&p3[page.p3_index() as usize]);
+        };
+
+        let Some(p1) = p2.next_table(page.p2_index()) else {
+            return get(&p2[page.p2_index() as usize]);
+        };
+
+        get(&p1[page.p1_index() as usize])
+    }
+
     /// Change the flags of the frame
     ///
     /// # Safety
@@ -784,4 +811,44 @@ impl<Root: RootLevel> Mapper<Root> {
         // SAFETY: Whever the frame is valid or not is handled by the user of this function
         allocator.deallocate_frame_any(unsafe { self.unmap_page(page) });
     }
+
+    /// Unmap and deallocate every page mapped through the p4 entries in `p4_range`, along with the p2
+    /// and p1 tables. The p3 tables are kept (but emptied) since other p4 tables might share them
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that every page in the range was mapped by [`Self::map`] (or to a frame
+    /// owned by the mapping), and that nothing references these pages anymore
+    pub unsafe fn clear<A>(&mut self, p4_range: Range<u64>, allocator: &mut A)
+    where
+        A: FrameAllocator,
+    {
+        for p4_index in p4_range {
+            let Some(p3) = self.p4_mut().next_table_mut(p4_index) else {
+                continue;
+            };
+
+            for p3_index in 0..ENTRY_COUNT {
+                if let Some(frame) = p3[p3_index as usize].pointed_frame() {
+                    allocator.deallocate_frame(frame);
+                } else if let Some(p2) = p3.next_table_mut(p3_index) {
+                    for p2_index in 0..ENTRY_COUNT {
+                        if let Some(frame) = p2[p2_index as usize].pointed_frame() {
+                            allocator.deallocate_frame(frame);
+                        } else if let Some(p1) = p2.next_table_mut(p2_index) {
+                            p1.entries.iter().filter_map(Entry::pointed_frame).for_each(|frame| {
+                                allocator.deallocate_frame(frame);
+                            });
+                            allocator.deallocate_frame(p2[p2_index as usize].pointed_table().expect("p1 table"));
+                        }
+                        p2[p2_index as usize].set_unused();
+                    }
+                    allocator.deallocate_frame(p3[p3_index as usize].pointed_table().expect("p2 table"));
+                }
+                p3[p3_index as usize].set_unused();
+            }
+        }
+
+        tlb::full_flush();
+    }
 }
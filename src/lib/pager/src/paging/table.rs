@@ -245,6 +245,8 @@ where
                     .copy_from_slice(&table[START as usize..END as usize])
             })
         };
+        // The entries now live in the active table, the old ones were copied to a new frame above
+        context.allocator.deallocate_frame(new_table.p4_frame);
 
         tlb::full_flush();
 
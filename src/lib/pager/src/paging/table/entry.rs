@@ -2,7 +2,7 @@ use core::{fmt::Debug, marker::PhantomData};
 
 use crate::{
     PageLevel,
-    address::{Frame, PageSize, PhysAddr},
+    address::{Frame, PageSize, PhysAddr, Size4K},
     paging::table::TableLevel,
 };
 
@@ -58,6 +58,19 @@ impl<L: TableLevel> Entry<L> {
         Some(Frame::containing_address(unsafe { PhysAddr::new_unchecked(self.value & 0x000fffff_fffff000) }))
     }
 
+    /// The frame of the next level table the entry points to, [`None`] if the entry maps a page
+    pub fn pointed_table(&self) -> Option<Frame<Size4K>> {
+        let flags = self.flags();
+        if L::PageSize::LEVEL == PageLevel::Page4K
+            || !flags.contains(EntryFlags::PRESENT)
+            || flags.contains(EntryFlags::HUGE_PAGE)
+        {
+            return None;
+        }
+
+        Some(Frame::containing_address(PhysAddr::new(self.mask_flags())))
+    }
+
     pub fn set<S: PageSize>(&mut self, frame: Frame<S>, flags: EntryFlags) {
         assert!(frame.start_address().as_u64() & !0x000fffff_fffff000 == 0);
         self.value = frame.start_address().as_u64() | flags.bits();
@@ -109,6 +109,30 @@ impl SystemCallStar {
     }
 }
 
+/// Derived from
+///
+/// https://www.felixcloutier.com/x86/syscall
+pub struct SystemCallFMask;
+
+impl SystemCallFMask {
+    /// Intel sdm vol 4, page 62
+    const IA32_FMASK_MSR: Msr = Msr::new(0xc0000084);
+
+    /// Read from [Self::IA32_FMASK_MSR] as the [`RFlags`] cleared by the syscall instruction
+    pub fn read() -> RFlags {
+        RFlags::from_bits_retain(unsafe { Self::IA32_FMASK_MSR.read() })
+    }
+
+    /// Write to the [Self::IA32_FMASK_MSR], the syscall instruction clears every flag in `flags`
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure that the syscall function handles the flags that are not cleared
+    pub unsafe fn write(flags: RFlags) {
+        unsafe { Self::IA32_FMASK_MSR.write(flags.bits()) };
+    }
+}
+
 pub struct KernelGsBase;
 pub struct GsBase;
 
@@ -351,6 +351,31 @@ impl<'a> Elf<'a> {
         }
     }
 
+    /// The address of the program headers once the elf is loaded, [`None`] if they're not part of a
+    /// loaded segment
+    pub fn program_headers_address(&self) -> Option<VirtAddr> {
+        let offset = self.reader.program_header_table_offset();
+        if let Some(phdr) =
+            self.reader.program_header_iter().find(|segment| segment.segment_type() == ProgramType::PHDR)
+        {
+            return Some(phdr.vaddr());
+        }
+
+        self.reader
+            .program_header_iter()
+            .filter(|segment| segment.segment_type() == ProgramType::Load)
+            .find(|segment| (segment.offset()..segment.offset() + segment.filesize()).contains(&offset))
+            .map(|segment| segment.vaddr() + (offset - segment.offset()))
+    }
+
+    pub fn program_header_count(&self) -> usize {
+        self.reader.program_entries_len()
+    }
+
+    pub fn program_header_size(&self) -> usize {
+        self.reader.program_entry_size()
+    }
+
     pub fn max_alignment(&self) -> usize {
         self.max_alignment
     }
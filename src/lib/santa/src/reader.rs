@@ -128,6 +128,14 @@ impl<'a> ElfReader<'a> {
         self.header().program_entries_len as usize
     }
 
+    pub fn program_entry_size(&self) -> usize {
+        self.header().program_entry_size as usize
+    }
+
+    pub fn program_header_table_offset(&self) -> u64 {
+        self.header().program_header_table_offset
+    }
+
     pub fn program_header_iter(&self) -> ProgramHeaderIter<'_> {
         ProgramHeaderIter { reader: self, index: 0 }
     }
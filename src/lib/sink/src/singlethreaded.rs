@@ -37,6 +37,72 @@ impl<T, const N: usize> Default for RingBuffer<T, N> {
     }
 }
 
+/// A bounded FIFO buffer that never overwrites, writing to a full buffer writes nothing. Meant for
+/// moving chunks of plain data (e.g. bytes of a pipe).
+pub struct BoundedBuffer<T: Copy, const N: usize> {
+    buffer: [T; N],
+    /// The index of the oldest element
+    start: usize,
+    len: usize,
+}
+
+impl<T: Copy + Default, const N: usize> BoundedBuffer<T, N> {
+    pub fn new() -> Self {
+        Self { buffer: [T::default(); N], start: 0, len: 0 }
+    }
+}
+
+impl<T: Copy, const N: usize> BoundedBuffer<T, N> {
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// The number of elements that can be written before the buffer is full
+    pub const fn free(&self) -> usize {
+        N - self.len
+    }
+
+    /// Append as many elements of `values` as there's free space for, returns the number written
+    pub fn write(&mut self, values: &[T]) -> usize {
+        let count = values.len().min(self.free());
+        let end = (self.start + self.len) % N;
+
+        let first = count.min(N - end);
+        self.buffer[end..end + first].copy_from_slice(&values[..first]);
+        self.buffer[..count - first].copy_from_slice(&values[first..count]);
+
+        self.len += count;
+        count
+    }
+
+    /// Move the oldest elements into `destination`, returns the number read
+    pub fn read(&mut self, destination: &mut [T]) -> usize {
+        let count = destination.len().min(self.len);
+
+        let first = count.min(N - self.start);
+        destination[..first].copy_from_slice(&self.buffer[self.start..self.start + first]);
+        destination[first..count].copy_from_slice(&self.buffer[..count - first]);
+
+        self.start = (self.start + count) % N;
+        self.len -= count;
+        count
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for BoundedBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,4 +176,49 @@ mod tests {
         buffer.write(60);
         assert_eq!(buffer.read(), Some(60));
     }
+
+    #[test]
+    pub fn bounded_read_write() {
+        let mut buffer = BoundedBuffer::<u8, 8>::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.write(&[1, 2, 3]), 3);
+        assert_eq!(buffer.len(), 3);
+
+        let mut out = [0; 2];
+        assert_eq!(buffer.read(&mut out), 2);
+        assert_eq!(out, [1, 2]);
+
+        let mut out = [0; 4];
+        assert_eq!(buffer.read(&mut out), 1);
+        assert_eq!(out[0], 3);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.read(&mut out), 0);
+    }
+
+    #[test]
+    pub fn bounded_full_doesnt_overwrite() {
+        let mut buffer = BoundedBuffer::<u8, 4>::new();
+        assert_eq!(buffer.write(&[1, 2, 3, 4, 5, 6]), 4);
+        assert!(buffer.is_full());
+        assert_eq!(buffer.write(&[7]), 0);
+
+        let mut out = [0; 8];
+        assert_eq!(buffer.read(&mut out), 4);
+        assert_eq!(out[..4], [1, 2, 3, 4]);
+    }
+
+    #[test]
+    pub fn bounded_wrap_around() {
+        let mut buffer = BoundedBuffer::<u8, 5>::new();
+        let mut out = [0; 5];
+
+        assert_eq!(buffer.write(&[1, 2, 3, 4]), 4);
+        assert_eq!(buffer.read(&mut out[..3]), 3);
+        assert_eq!(buffer.write(&[5, 6, 7, 8]), 4);
+        assert!(buffer.is_full());
+
+        assert_eq!(buffer.read(&mut out), 5);
+        assert_eq!(out, [4, 5, 6, 7, 8]);
+        assert!(buffer.is_empty());
+    }
 }